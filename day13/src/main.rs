@@ -1,8 +1,9 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
 use std::cmp;
-use std::collections::VecDeque;
 use std::env;
 use std::fmt;
 use std::fs;
+use std::time::{Duration, Instant};
 
 /*
 --- Day 13: Distress Signal ---
@@ -181,7 +182,7 @@ Organize all of the packets into the correct order. What is the decoder key for
 
 */
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum Item {
     Value(u32),
     List(Vec<Item>),
@@ -213,146 +214,545 @@ impl fmt::Display for Item {
         }
     }
 }
-fn slurp_list(bytes: &[u8]) -> (usize, Vec<Item>) {
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PacketErrorReason {
+    UnexpectedCharacter(char),
+    UnexpectedEnd,    // nothing where a packet should be
+    UnterminatedList, // ran out of bytes before the closing ]
+    TrailingGarbage,  // more after the outer list closed
+    NumberTooLarge,
+    TooDeep, // more than MAX_DEPTH lists open at once
+}
+
+// Parsing, printing, comparing and even dropping items all recurse once per
+// level, so a deep enough packet would overflow the stack. serde_json stops
+// at the same depth
+const MAX_DEPTH: usize = 127;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PacketError {
+    offset: usize, // byte in the line where things went wrong
+    reason: PacketErrorReason,
+}
+
+impl fmt::Display for PacketError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "at byte {}: ", self.offset)?;
+        return match self.reason {
+            PacketErrorReason::UnexpectedCharacter(c) => write!(f, "unexpected character {:?}", c),
+            PacketErrorReason::UnexpectedEnd => write!(f, "expected a packet"),
+            PacketErrorReason::UnterminatedList => write!(f, "list is never closed"),
+            PacketErrorReason::TrailingGarbage => write!(f, "trailing garbage after packet"),
+            PacketErrorReason::NumberTooLarge => write!(f, "number too large"),
+            PacketErrorReason::TooDeep => write!(f, "more than {} lists deep", MAX_DEPTH),
+        };
+    }
+}
+
+// Reads the rest of a list, start is the position just after its [ and depth
+// counts the lists open including this one. Returns the position just after
+// the matching ] along with the items. Offsets are all relative to the start
+// of bytes so errors can point at the right place
+fn slurp_list(bytes: &[u8], start: usize, depth: usize) -> Result<(usize, Vec<Item>), PacketError> {
     let mut out: Vec<Item> = vec![];
-    let mut pos = 0;
-    let mut number_str: String = Default::default();
+    let mut pos = start;
+
+    // after [ or , we need an item, after an item we need , or ]
+    let mut want_item = true;
     while pos < bytes.len() {
         //println!("Match @{}, {}", pos, bytes[pos] as char);
         match bytes[pos] {
-            b'[' => {
-                pos += 1;
+            b'[' if want_item => {
+                if depth >= MAX_DEPTH {
+                    return Err(PacketError {
+                        offset: pos,
+                        reason: PacketErrorReason::TooDeep,
+                    });
+                }
+
                 //println!("start from @{} {}..", pos, bytes[pos] as char);
-                let (eaten, v) = slurp_list(&bytes[pos..]);
-                //println!("done with ..{} @{}", bytes[pos] as char, eaten);
+                let (end, v) = slurp_list(bytes, pos + 1, depth + 1)?;
+                //println!("done with ..{} @{}", bytes[pos] as char, end);
                 out.push(Item::List(v));
-                pos += eaten;
+                pos = end;
+                want_item = false;
             }
-            b',' => {
-                //println!("comma, done with {}", number_str);
-                if number_str.len() > 0 {
-                    out.push(Item::Value(number_str.parse().unwrap()));
-                    number_str.clear();
+            b'0'..=b'9' if want_item => {
+                let digits_start = pos;
+                while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+                    pos += 1;
                 }
+
+                // only ascii digits, so this can only fail by overflowing
+                let number_str = std::str::from_utf8(&bytes[digits_start..pos]).unwrap();
+                let value = number_str.parse().map_err(|_| PacketError {
+                    offset: digits_start,
+                    reason: PacketErrorReason::NumberTooLarge,
+                })?;
+                out.push(Item::Value(value));
+                want_item = false;
+            }
+            b',' if !want_item => {
                 pos += 1;
+                want_item = true;
             }
-            b']' => {
-                if number_str.len() > 0 {
-                    //println!("], done with {}", number_str);
-                    out.push(Item::Value(number_str.parse().unwrap()));
-                    number_str.clear();
-                }
+            // an empty list is fine, a trailing comma isn't
+            b']' if !want_item || out.is_empty() => {
                 // done with out section
-                pos += 1;
                 //println!("done with section, up");
-                return (pos, out);
+                return Ok((pos + 1, out));
             }
-            _ => {
-                // number
-                number_str.push(bytes[pos] as char);
-                //println!("updated {}", number_str);
-                pos += 1;
+            c => {
+                return Err(PacketError {
+                    offset: pos,
+                    reason: PacketErrorReason::UnexpectedCharacter(c as char),
+                });
             }
         }
     }
-    return (pos, out);
+
+    // point at the [ that was never closed
+    return Err(PacketError {
+        offset: start - 1,
+        reason: PacketErrorReason::UnterminatedList,
+    });
 }
 
 impl Item {
-    fn parse(line: &str) -> Item {
-        let (_, v) = slurp_list(line[1..].as_bytes());
-        //println!("Ended at {}", pos);
-        return Item::List(v);
+    // a packet is always a list, and must be the whole line
+    fn parse(line: &str) -> Result<Item, PacketError> {
+        let bytes = line.as_bytes();
+        match bytes.first() {
+            None => {
+                return Err(PacketError {
+                    offset: 0,
+                    reason: PacketErrorReason::UnexpectedEnd,
+                });
+            }
+            Some(b'[') => {}
+            Some(c) => {
+                return Err(PacketError {
+                    offset: 0,
+                    reason: PacketErrorReason::UnexpectedCharacter(*c as char),
+                });
+            }
+        }
+
+        let (end, v) = slurp_list(bytes, 1, 1)?;
+        //println!("Ended at {}", end);
+        if end != bytes.len() {
+            return Err(PacketError {
+                offset: end,
+                reason: PacketErrorReason::TrailingGarbage,
+            });
+        }
+        return Ok(Item::List(v));
     }
 }
 
-impl PartialEq for Item {
-    fn eq(&self, other: &Self) -> bool {
-        match self {
-            Item::Value(my_val) => match other {
-                Item::Value(other_val) => return my_val == other_val,
-                Item::List(other_list) => {
-                    let self_item = Item::Value(*my_val);
-                    let self_list: Vec<Self> = vec![self_item];
-                    return self_list == *other_list;
-                }
-            },
-            Item::List(my_list) => match other {
-                Item::Value(other_val) => {
-                    let other_item = Item::Value(*other_val);
-                    let other_list = vec![other_item];
-                    return *my_list == other_list;
+// Packets come in pairs separated by blank lines. Panics with the line number
+// if a packet doesn't parse
+fn parse_pairs(contents: &str) -> Vec<(Item, Item)> {
+    let mut packets = vec![];
+    for (i, line) in contents.lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        packets.push(Item::parse(line).unwrap_or_else(|e| panic!("Line {}: {}", i + 1, e)));
+    }
+
+    let mut pairs = vec![];
+    let mut packets = packets.into_iter();
+    while let Some(packet0) = packets.next() {
+        let packet1 = packets.next().expect("Packets should come in pairs");
+        pairs.push((packet0, packet1));
+    }
+    return pairs;
+}
+
+// Parses each line on its own and reports what's wrong with it, for checking
+// files of hand-written packets like bad_packets.txt. Packets are JSON, so
+// each line is also parsed as JSON to make sure the two parsers agree
+fn check(contents: &str) {
+    for (i, line) in contents.lines().enumerate() {
+        let json = serde_json::from_str::<serde_json::Value>(line);
+        match Item::parse(line) {
+            Ok(item) => {
+                println!("Line {}: ok {}", i + 1, item);
+                if json.ok() != Some(serde_json::Value::from(&item)) {
+                    println!("Line {}: JSON parser disagrees", i + 1);
                 }
-                Item::List(other_list) => {
-                    return my_list == other_list;
+            }
+            Err(e) => {
+                println!("Line {}: {:?} {}", i + 1, line, e);
+                if json.is_ok() {
+                    println!("Line {}: valid JSON though", i + 1);
                 }
-            },
+            }
         }
     }
 }
 
+#[derive(Debug)]
+enum JsonError {
+    Syntax(serde_json::Error),
+    NotAPacket(serde_json::Value), // only lists and u32s are allowed
+    NotAPair(serde_json::Value),
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            JsonError::Syntax(e) => write!(f, "bad JSON: {}", e),
+            JsonError::NotAPacket(v) => write!(f, "not a packet: {}", v),
+            JsonError::NotAPair(v) => write!(f, "expected a pair of packets: {}", v),
+        };
+    }
+}
+
+// every item has exactly one JSON form, so this and the conversion back are
+// lossless
+impl From<&Item> for serde_json::Value {
+    fn from(item: &Item) -> serde_json::Value {
+        return match item {
+            Item::Value(v) => serde_json::Value::from(*v),
+            Item::List(list) => serde_json::Value::Array(list.iter().map(|i| i.into()).collect()),
+        };
+    }
+}
+
+impl TryFrom<&serde_json::Value> for Item {
+    type Error = JsonError;
+
+    fn try_from(value: &serde_json::Value) -> Result<Item, JsonError> {
+        return match value {
+            serde_json::Value::Array(list) => Ok(Item::List(
+                list.iter().map(Item::try_from).collect::<Result<_, _>>()?,
+            )),
+            serde_json::Value::Number(n) => n
+                .as_u64()
+                .and_then(|n| u32::try_from(n).ok())
+                .map(Item::Value)
+                .ok_or_else(|| JsonError::NotAPacket(value.clone())),
+            _ => Err(JsonError::NotAPacket(value.clone())),
+        };
+    }
+}
+
+// a whole file of pairs as [[left, right], [left, right], ..]
+fn pairs_to_json(pairs: &[(Item, Item)]) -> serde_json::Value {
+    return serde_json::Value::Array(
+        pairs
+            .iter()
+            .map(|(packet0, packet1)| {
+                serde_json::Value::Array(vec![packet0.into(), packet1.into()])
+            })
+            .collect(),
+    );
+}
+
+fn pairs_from_json(contents: &str) -> Result<Vec<(Item, Item)>, JsonError> {
+    let value: serde_json::Value = serde_json::from_str(contents).map_err(JsonError::Syntax)?;
+    let pairs = match &value {
+        serde_json::Value::Array(pairs) => pairs,
+        _ => return Err(JsonError::NotAPair(value)),
+    };
+
+    let mut out = vec![];
+    for pair in pairs {
+        let (packet0, packet1) = match pair.as_array().map(|p| p.as_slice()) {
+            Some([packet0, packet1]) => (packet0, packet1),
+            _ => return Err(JsonError::NotAPair(pair.clone())),
+        };
+
+        // packets themselves are always lists, even if items needn't be
+        for packet in [packet0, packet1] {
+            if !packet.is_array() {
+                return Err(JsonError::NotAPacket(packet.clone()));
+            }
+        }
+        out.push((Item::try_from(packet0)?, Item::try_from(packet1)?));
+    }
+    return Ok(out);
+}
+
+// Equality and ordering all come from cmp, so they can't disagree
+impl PartialEq for Item {
+    fn eq(&self, other: &Self) -> bool {
+        return self.cmp(other) == cmp::Ordering::Equal;
+    }
+}
+
 impl Eq for Item {}
 
 impl PartialOrd for Item {
     fn partial_cmp(&self, other: &Item) -> Option<cmp::Ordering> {
-        match self {
-            Item::Value(my_val) => match other {
-                Item::Value(other_val) => return Some(my_val.cmp(other_val)),
-                Item::List(other_list) => {
-                    let self_item = Item::Value(*my_val);
-                    let self_list: Vec<Self> = vec![self_item];
-                    return Some(self_list.cmp(other_list));
-                }
-            },
-            Item::List(my_list) => match other {
-                Item::Value(other_val) => {
-                    let other_item = Item::Value(*other_val);
-                    let other_list = vec![other_item];
-                    return Some(my_list.cmp(&other_list));
-                }
-                Item::List(other_list) => {
-                    return Some(my_list.cmp(other_list));
-                }
-            },
-        }
+        return Some(self.cmp(other));
     }
 }
 
 impl Ord for Item {
+    // Slices compare item by item and then by length, which is exactly the
+    // puzzle's rule for lists. An integer compared to a list acts like a list
+    // holding just that integer, slice::from_ref gets that without a Vec
     fn cmp(&self, other: &Item) -> cmp::Ordering {
-        match self {
-            Item::Value(my_val) => match other {
-                Item::Value(other_val) => return my_val.cmp(other_val),
-                Item::List(other_list) => {
-                    let self_item = Item::Value(*my_val);
-                    let self_list: Vec<Self> = vec![self_item];
-                    return self_list.cmp(other_list);
+        return match (self, other) {
+            (Item::Value(my_val), Item::Value(other_val)) => my_val.cmp(other_val),
+            (Item::List(my_list), Item::List(other_list)) => my_list.cmp(other_list),
+            (Item::Value(_), Item::List(other_list)) => {
+                std::slice::from_ref(self).cmp(other_list.as_slice())
+            }
+            (Item::List(my_list), Item::Value(_)) => {
+                my_list.as_slice().cmp(std::slice::from_ref(other))
+            }
+        };
+    }
+}
+
+// One line of the explanation in the puzzle's walkthrough
+#[derive(Debug)]
+enum Step {
+    Compare(Item, Item),
+    ConvertLeft(u32), // mixed types, the left integer becomes a list
+    ConvertRight(u32),
+    LeftSmaller,
+    RightSmaller,
+    LeftRanOut,
+    RightRanOut,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Step::Compare(left, right) => write!(f, "Compare {} vs {}", left, right),
+            Step::ConvertLeft(v) => write!(
+                f,
+                "Mixed types; convert left to [{}] and retry comparison",
+                v
+            ),
+            Step::ConvertRight(v) => write!(
+                f,
+                "Mixed types; convert right to [{}] and retry comparison",
+                v
+            ),
+            Step::LeftSmaller => {
+                write!(f, "Left side is smaller, so inputs are in the right order")
+            }
+            Step::RightSmaller => write!(
+                f,
+                "Right side is smaller, so inputs are not in the right order"
+            ),
+            Step::LeftRanOut => write!(
+                f,
+                "Left side ran out of items, so inputs are in the right order"
+            ),
+            Step::RightRanOut => write!(
+                f,
+                "Right side ran out of items, so inputs are not in the right order"
+            ),
+        };
+    }
+}
+
+// Every step taken comparing two packets, each with how deep in the tree it
+// happened. Stops at the first step that decides the order
+#[derive(Debug)]
+struct Trace {
+    steps: Vec<(usize, Step)>,
+    ordering: cmp::Ordering,
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (depth, step) in self.steps.iter() {
+            writeln!(f, "{}- {}", "  ".repeat(*depth), step)?;
+        }
+        return Ok(());
+    }
+}
+
+impl Item {
+    // same answer as cmp, but writes down how it got there
+    fn explain(&self, other: &Item) -> Trace {
+        let mut steps = vec![];
+        let ordering = self.compare_traced(other, 0, &mut steps);
+        return Trace {
+            steps: steps,
+            ordering: ordering,
+        };
+    }
+
+    fn compare_traced(
+        &self,
+        other: &Item,
+        depth: usize,
+        steps: &mut Vec<(usize, Step)>,
+    ) -> cmp::Ordering {
+        steps.push((depth, Step::Compare(self.clone(), other.clone())));
+        match (self, other) {
+            (Item::Value(my_val), Item::Value(other_val)) => {
+                let ordering = my_val.cmp(other_val);
+                match ordering {
+                    cmp::Ordering::Less => steps.push((depth + 1, Step::LeftSmaller)),
+                    cmp::Ordering::Greater => steps.push((depth + 1, Step::RightSmaller)),
+                    cmp::Ordering::Equal => {}
                 }
-            },
-            Item::List(my_list) => match other {
-                Item::Value(other_val) => {
-                    let other_item = Item::Value(*other_val);
-                    let other_list = vec![other_item];
-                    return my_list.cmp(&other_list);
+                return ordering;
+            }
+            (Item::List(my_list), Item::List(other_list)) => {
+                for (mine, theirs) in my_list.iter().zip(other_list.iter()) {
+                    let ordering = mine.compare_traced(theirs, depth + 1, steps);
+                    if ordering != cmp::Ordering::Equal {
+                        return ordering;
+                    }
                 }
-                Item::List(other_list) => {
-                    return my_list.cmp(other_list);
+
+                let ordering = my_list.len().cmp(&other_list.len());
+                match ordering {
+                    cmp::Ordering::Less => steps.push((depth + 1, Step::LeftRanOut)),
+                    cmp::Ordering::Greater => steps.push((depth + 1, Step::RightRanOut)),
+                    cmp::Ordering::Equal => {}
                 }
-            },
+                return ordering;
+            }
+            (Item::Value(my_val), Item::List(_)) => {
+                steps.push((depth + 1, Step::ConvertLeft(*my_val)));
+                let my_list = Item::List(vec![Item::Value(*my_val)]);
+                return my_list.compare_traced(other, depth + 1, steps);
+            }
+            (Item::List(_), Item::Value(other_val)) => {
+                steps.push((depth + 1, Step::ConvertRight(*other_val)));
+                let other_list = Item::List(vec![Item::Value(*other_val)]);
+                return self.compare_traced(&other_list, depth + 1, steps);
+            }
+        }
+    }
+}
+
+// the walkthrough from the puzzle for every pair
+fn explain(pairs: &[(Item, Item)]) {
+    for (i, (packet0, packet1)) in pairs.iter().enumerate() {
+        let trace = packet0.explain(packet1);
+        println!("== Pair {} ==", i + 1);
+        print!("{}", trace);
+        if trace.ordering != packet0.cmp(packet1) {
+            println!(
+                "!! trace says {:?} but cmp says {:?}",
+                trace.ordering,
+                packet0.cmp(packet1)
+            );
         }
+        println!();
     }
 }
 
-fn part1(contents: &str) {
-    let mut lines = contents.lines();
+// Cheap xorshift so generated files are the same every run
+struct Rng(u64);
 
-    let mut packet_number = 1;
-    let mut sum = 0;
-    loop {
-        let packet0 = Item::parse(lines.next().unwrap());
-        let packet1 = Item::parse(lines.next().unwrap());
+impl Rng {
+    fn below(&mut self, n: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        return self.0 % n;
+    }
+
+    // lots of short lists and small numbers, so pairs often share prefixes
+    // and integers often get compared to lists
+    fn item(&mut self, depth: usize) -> Item {
+        if depth > 0 && self.below(3) == 0 {
+            return Item::Value(self.below(11) as u32);
+        }
+        if depth >= 4 {
+            return Item::Value(self.below(11) as u32);
+        }
+        let len = self.below(6);
+        return Item::List((0..len).map(|_| self.item(depth + 1)).collect());
+    }
+}
+
+// a puzzle style file of random pairs
+fn generate(pairs: usize, fname: &str) {
+    let mut rng = Rng(0x2022_1213);
+    let mut out = String::new();
+    for i in 0..pairs {
+        if i > 0 {
+            out.push('\n');
+        }
+        out.push_str(&format!("{}\n{}\n", rng.item(0), rng.item(0)));
+    }
+    fs::write(fname, out).expect("Should be able to write packets");
+}
+
+// The old way of comparing, wrapping integers in a fresh Vec whenever they
+// meet a list. Only kept so the benchmark has something to beat
+fn cmp_wrapping(left: &Item, right: &Item) -> cmp::Ordering {
+    return match (left, right) {
+        (Item::Value(my_val), Item::Value(other_val)) => my_val.cmp(other_val),
+        (Item::List(my_list), Item::List(other_list)) => {
+            for (mine, theirs) in my_list.iter().zip(other_list.iter()) {
+                let ordering = cmp_wrapping(mine, theirs);
+                if ordering != cmp::Ordering::Equal {
+                    return ordering;
+                }
+            }
+            my_list.len().cmp(&other_list.len())
+        }
+        (Item::Value(my_val), Item::List(_)) => {
+            cmp_wrapping(&Item::List(vec![Item::Value(*my_val)]), right)
+        }
+        (Item::List(_), Item::Value(other_val)) => {
+            cmp_wrapping(left, &Item::List(vec![Item::Value(*other_val)]))
+        }
+    };
+}
+
+type Comparison = fn(&Item, &Item) -> cmp::Ordering;
 
-        match packet0.cmp(&packet1) {
+// sort every packet a few times with each comparison, e.g.
+// `--generate 200000 big.txt` then `big.txt --bench`
+fn bench(pairs: &[(Item, Item)]) {
+    let mut packets: Vec<Item> = vec![];
+    for (packet0, packet1) in pairs {
+        packets.push(packet0.clone());
+        packets.push(packet1.clone());
+    }
+
+    // only the sort is timed, not copying the unsorted packets
+    let rounds = 5;
+    let comparisons: [(&str, Comparison); 2] = [("wrapping", cmp_wrapping), ("cmp", Item::cmp)];
+    let mut sorted: Vec<Vec<Item>> = vec![];
+    for (name, compare) in comparisons {
+        let mut elapsed = Duration::ZERO;
+        let mut out = vec![];
+        for _ in 0..rounds {
+            out = packets.clone();
+            let start = Instant::now();
+            out.sort_by(compare);
+            elapsed += start.elapsed();
+        }
+        println!(
+            "{}: {} packets, {:?} per sort",
+            name,
+            packets.len(),
+            elapsed / rounds
+        );
+        sorted.push(out);
+    }
+
+    // both orders should be the same
+    let agree = sorted[0]
+        .iter()
+        .zip(sorted[1].iter())
+        .all(|(a, b)| cmp_wrapping(a, b) == cmp::Ordering::Equal);
+    println!("Orders agree: {}", agree);
+}
+
+fn part1(pairs: &[(Item, Item)]) {
+    let mut sum = 0;
+    for (i, (packet0, packet1)) in pairs.iter().enumerate() {
+        let packet_number = i + 1;
+        match packet0.cmp(packet1) {
             cmp::Ordering::Less => {
                 println!("in order:\n{}\n{}", packet0, packet1);
                 sum += packet_number;
@@ -365,26 +765,16 @@ fn part1(contents: &str) {
                 println!("out of order: \n{}\n{}", packet0, packet1);
             }
         }
-
-        packet_number += 1;
-        if !lines.next().is_some() {
-            break;
-        }
     }
     println!("Sum of ordered: {}", sum);
 }
 
-fn part2(contents: &str) {
-    let mut lines = contents.lines();
-
+fn part2(pairs: &[(Item, Item)]) {
+    // sorting moves them around, so work on a copy
     let mut packets: Vec<Item> = vec![];
-    loop {
-        packets.push(Item::parse(lines.next().unwrap()));
-        packets.push(Item::parse(lines.next().unwrap()));
-
-        if !lines.next().is_some() {
-            break;
-        }
+    for (packet0, packet1) in pairs {
+        packets.push(packet0.clone());
+        packets.push(packet1.clone());
     }
 
     let div1 = Item::List(vec![Item::Value(2)]);
@@ -405,9 +795,168 @@ fn part2(contents: &str) {
 }
 
 fn main() {
-    let fname = env::args().nth(1).expect("Should pass 1 filename arg");
-    let contents = fs::read_to_string(fname).expect("Should have been able to read the file");
+    let usage = "Usage: <filename> [--check] [--explain] [--json] [--to-json <out>] [--bench] \
+                 | --generate <pairs> <out>";
+    let mut fname = None;
+    let mut check_lines = false;
+    let mut from_json = false;
+    let mut json_out = None;
+    let mut explain_pairs = false;
+    let mut run_bench = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => {
+                check_lines = true;
+            }
+            "--explain" => {
+                explain_pairs = true;
+            }
+            "--bench" => {
+                run_bench = true;
+            }
+            "--generate" => {
+                let pairs = args.next().expect(usage).parse().expect(usage);
+                generate(pairs, &args.next().expect(usage));
+                return;
+            }
+            "--json" => {
+                from_json = true;
+            }
+            "--to-json" => {
+                json_out = Some(args.next().expect(usage));
+            }
+            _ => {
+                fname = Some(arg);
+            }
+        }
+    }
+
+    let contents =
+        fs::read_to_string(fname.expect(usage)).expect("Should have been able to read the file");
+    if check_lines {
+        check(&contents);
+        return;
+    }
+
+    let pairs = if from_json {
+        pairs_from_json(&contents).unwrap_or_else(|e| panic!("{}", e))
+    } else {
+        parse_pairs(&contents)
+    };
+
+    // just convert, don't solve
+    if let Some(out) = json_out {
+        let json = pairs_to_json(&pairs);
+        fs::write(out, format!("{}\n", json)).expect("Should be able to write JSON");
+        return;
+    }
+
+    if run_bench {
+        bench(&pairs);
+        return;
+    }
+
+    if explain_pairs {
+        explain(&pairs);
+        return;
+    }
+
+    part1(&pairs);
+    part2(&pairs);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(line: &str) -> PacketError {
+        return Item::parse(line).unwrap_err();
+    }
+
+    fn at(offset: usize, reason: PacketErrorReason) -> PacketError {
+        return PacketError {
+            offset: offset,
+            reason: reason,
+        };
+    }
+
+    #[test]
+    fn unexpected_characters() {
+        use PacketErrorReason::UnexpectedCharacter;
+        assert_eq!(error("[1,2 ]"), at(4, UnexpectedCharacter(' ')));
+        assert_eq!(error("[1,a]"), at(3, UnexpectedCharacter('a')));
+        assert_eq!(error("x[1]"), at(0, UnexpectedCharacter('x')));
+    }
+
+    #[test]
+    fn misplaced_commas() {
+        use PacketErrorReason::UnexpectedCharacter;
+        assert_eq!(error("[1,,2]"), at(3, UnexpectedCharacter(',')));
+        assert_eq!(error("[1,2,]"), at(5, UnexpectedCharacter(']')));
+        assert_eq!(error("[,1]"), at(1, UnexpectedCharacter(',')));
+    }
+
+    #[test]
+    fn empty_line() {
+        assert_eq!(error(""), at(0, PacketErrorReason::UnexpectedEnd));
+    }
+
+    #[test]
+    fn unterminated_lists() {
+        assert_eq!(error("[[1,2]"), at(0, PacketErrorReason::UnterminatedList));
+        assert_eq!(error("[1,2"), at(0, PacketErrorReason::UnterminatedList));
+        assert_eq!(error("[1,[2"), at(3, PacketErrorReason::UnterminatedList));
+    }
 
-    part1(&contents);
-    part2(&contents);
+    #[test]
+    fn too_deep() {
+        let deepest = "[".repeat(MAX_DEPTH) + &"]".repeat(MAX_DEPTH);
+        assert_eq!(Item::parse(&deepest).unwrap().to_string(), deepest);
+
+        let too_deep = PacketError {
+            offset: MAX_DEPTH,
+            reason: PacketErrorReason::TooDeep,
+        };
+        let deeper = "[".repeat(MAX_DEPTH + 1) + &"]".repeat(MAX_DEPTH + 1);
+        assert_eq!(error(&deeper), too_deep);
+        assert_eq!(error(&"[".repeat(200000)), too_deep);
+        let balanced = "[".repeat(200000) + &"]".repeat(200000);
+        assert_eq!(error(&balanced), too_deep);
+    }
+
+    #[test]
+    fn trailing_garbage() {
+        assert_eq!(error("[1,2]]"), at(5, PacketErrorReason::TrailingGarbage));
+        assert_eq!(error("[1,2]x"), at(5, PacketErrorReason::TrailingGarbage));
+    }
+
+    #[test]
+    fn number_too_large() {
+        assert_eq!(
+            error("[99999999999]"),
+            at(1, PacketErrorReason::NumberTooLarge)
+        );
+        assert_eq!(
+            error("[1,4294967296]"),
+            at(3, PacketErrorReason::NumberTooLarge)
+        );
+    }
+
+    #[test]
+    fn valid_packets() {
+        // Display prints exactly what was parsed, so round trip through it
+        for line in [
+            "[]",
+            "[[]]",
+            "[[[]],[]]",
+            "[1,[2,3],[]]",
+            "[4294967295]",
+            "[10,[[0]]]",
+        ] {
+            let item = Item::parse(line).unwrap_or_else(|e| panic!("{}: {}", line, e));
+            assert_eq!(item.to_string(), line);
+        }
+    }
 }
@@ -1,10 +1,11 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
 use ndarray::prelude::*;
 use ndarray::Array2;
 use std::cmp;
-use std::collections::VecDeque;
 use std::env;
-use std::fmt;
 use std::fs;
+use std::io::Write;
 
 /*
 --- Day 14: Regolith Reservoir ---
@@ -167,22 +168,135 @@ units of sand come to rest?
 
 */
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct World {
     state: Array2<u8>,
     origin: Point,
     input: Point, // x,y of where sand comes in
+
+    // part 2 rules, an infinite row of rock just below the bottom of the grid
+    floor: bool,
+
+    // grid positions the last grain passed through, the next grain follows
+    // the same path so it can start from where the last one settled
+    path: Vec<Point>,
+
+    particle: Particle, // what gets poured in
+}
+
+// How a grain moves. Each step it tries offsets in order and takes the first
+// that isn't blocked, if none are free it settles and is drawn as glyph. A
+// grain never steps straight back to where it just was, so particles that
+// move sideways can't bounce back and forth forever
+#[derive(Debug, Clone)]
+struct Particle {
+    offsets: Vec<(i32, i32)>,
+    blocked_by: Vec<u8>, // cells it can't move into, its own glyph always blocks
+    glyph: u8,
+    color: [u8; 3], // settled cells in frames
+}
+
+impl Particle {
+    // down, then down-left, then down-right
+    fn sand() -> Particle {
+        return Particle {
+            offsets: vec![(0, 1), (-1, 1), (1, 1)],
+            blocked_by: b"#+".to_vec(),
+            glyph: b'o',
+            color: [194, 178, 128],
+        };
+    }
+
+    // prefers falling down-right over down-left
+    fn sand_right() -> Particle {
+        return Particle {
+            offsets: vec![(0, 1), (1, 1), (-1, 1)],
+            ..Particle::sand()
+        };
+    }
+
+    // falls straight down, otherwise spreads sideways
+    fn water() -> Particle {
+        return Particle {
+            offsets: vec![(0, 1), (-1, 0), (1, 0)],
+            blocked_by: b"#+".to_vec(),
+            glyph: b'~',
+            color: [64, 96, 255],
+        };
+    }
+
+    fn from_name(name: &str) -> Option<Particle> {
+        return match name {
+            "sand" => Some(Particle::sand()),
+            "sand-right" => Some(Particle::sand_right()),
+            "water" => Some(Particle::water()),
+            _ => None,
+        };
+    }
+
+    // the glyph doesn't change how it piles up
+    fn moves_like_sand(&self) -> bool {
+        let sand = Particle::sand();
+        return self.offsets == sand.offsets && self.blocked_by == sand.blocked_by;
+    }
+
+    fn is_blocked_by(&self, cell: u8) -> bool {
+        return cell == self.glyph || self.blocked_by.contains(&cell);
+    }
 }
 
 #[derive(Debug)]
 enum SandResult {
     Oblivion,
+    OffGrid,
     Falling,
     Settled,
     Plugged,
 }
 
+// why a simulation stopped
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Termination {
+    Oblivion,   // a grain fell out of the bottom (or off the side) of the cave
+    OffGrid,    // with a floor, a grain went past the side of the grid
+    Plugged,    // the source is buried
+    StepLimit,  // a grain was still moving after the step limit
+    GrainLimit, // poured as many grains as allowed
+}
+
+// None means work the limit out from the size of the cave
+#[derive(Debug, Clone, Copy, Default)]
+struct Limits {
+    steps: Option<usize>,  // per grain
+    grains: Option<usize>, // per simulation
+}
+
 #[derive(Debug)]
+struct SimulationReport {
+    grains: usize, // how many settled
+    termination: Termination,
+    settled_bounds: Option<(Point, Point)>, // min and max corner, in cave coordinates
+}
+
+impl std::fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} grains settled, stopped by {:?}",
+            self.grains, self.termination
+        )?;
+        if let Some((min, max)) = self.settled_bounds {
+            write!(
+                f,
+                ", settled within {},{} -> {},{}",
+                min.x, min.y, max.x, max.y
+            )?;
+        }
+        return Ok(());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 struct Point {
     x: i32,
     y: i32,
@@ -192,101 +306,127 @@ struct Point {
 struct Line {
     from: Point,
     to: Point,
+    path: usize, // input line the segment came from, 1-based
+}
+
+impl Line {
+    fn is_axis_aligned(&self) -> bool {
+        return self.from.x == self.to.x || self.from.y == self.to.y;
+    }
+
+    // every cell on the segment, ends included
+    // https://en.wikipedia.org/wiki/Bresenham%27s_line_algorithm
+    fn points(&self) -> Vec<Point> {
+        let dx = (self.to.x - self.from.x).abs();
+        let dy = -(self.to.y - self.from.y).abs();
+        let sx = (self.to.x - self.from.x).signum();
+        let sy = (self.to.y - self.from.y).signum();
+
+        let mut out = vec![];
+        let mut p = self.from;
+        let mut err = dx + dy;
+        loop {
+            out.push(p);
+            if p.x == self.to.x && p.y == self.to.y {
+                break;
+            }
+
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                p.x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                p.y += sy;
+            }
+        }
+        return out;
+    }
+}
+
+fn parse_lines(contents: &str) -> Vec<Line> {
+    let mut lines: Vec<Line> = vec![];
+    for (i, line) in contents.lines().enumerate() {
+        let mut last = None;
+        for pos in line.split(" -> ") {
+            let maybe_spl = pos.split_once(",");
+            if maybe_spl.is_none() {
+                continue;
+            }
+            let (xs, ys) = maybe_spl.unwrap();
+            let x: i32 = xs.parse().unwrap();
+            let y: i32 = ys.parse().unwrap();
+
+            let curr_pt = Point { x: x, y: y };
+            if let Some(prev_pt) = last {
+                lines.push(Line {
+                    from: prev_pt,
+                    to: curr_pt,
+                    path: i + 1,
+                });
+            }
+            last = Some(curr_pt);
+        }
+    }
+    return lines;
 }
 
 impl std::fmt::Display for World {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         for row in self.state.rows() {
             for v in row {
-                write!(f, "{}", *v as char);
+                write!(f, "{}", *v as char)?;
             }
-            write!(f, "\n");
+            writeln!(f)?;
+        }
+        if self.floor {
+            writeln!(f, "{}", "#".repeat(self.state.ncols()))?;
         }
         return Ok(());
     }
 }
 
 impl World {
-    fn parse(contents: &str, make_floor: bool) -> World {
+    // The grid is sized so it can hold everything that piles up on the
+    // floor, but the floor itself is only switched on by the part 2 rules
+    fn parse(contents: &str) -> World {
         // read lines and compute bounds
         let mut min_x = 500;
         let mut max_x = 500;
         let mut min_y = 0;
         let mut max_y = 0;
-        let mut lines: Vec<Line> = vec![];
-        for line in contents.lines() {
-            let mut last = None;
-            for pos in line.split(" -> ") {
-                let maybe_spl = pos.split_once(",");
-                if !maybe_spl.is_some() {
-                    continue;
-                }
-                let (xs, ys) = maybe_spl.unwrap();
-                let x: i32 = xs.parse().unwrap();
-                let y: i32 = ys.parse().unwrap();
-
-                min_x = cmp::min(x, min_x);
-                max_x = cmp::max(x, max_x);
-                min_y = cmp::min(y, min_y);
-                max_y = cmp::max(y, max_y);
-
-                let curr_pt = Point { x: x, y: y };
-                match last {
-                    Some(prev_pt) => {
-                        lines.push(Line {
-                            from: prev_pt,
-                            to: curr_pt,
-                        });
-                    }
-                    _ => {}
-                }
-                last = Some(Point { x: x, y: y });
+        let lines = parse_lines(contents);
+        for line in lines.iter() {
+            for p in [&line.from, &line.to] {
+                min_x = cmp::min(p.x, min_x);
+                max_x = cmp::max(p.x, max_x);
+                min_y = cmp::min(p.y, min_y);
+                max_y = cmp::max(p.y, max_y);
             }
         }
 
         let floor_y = max_y + 2;
 
-        // allocate grid, pad by alot to help with bounds checking or weird
-        // cases where sand needs to fall off known platforms
-        min_x -= 150;
-        min_y -= 4;
-        max_x += 150;
-        max_y += 4;
+        // sand moves at most one column sideways per row it falls, so nothing
+        // can land further out than floor_y either side of the source. Pad one
+        // more column so sand that misses every rock can fall past it
+        min_x = cmp::min(min_x, 500 - floor_y) - 1;
+        max_x = cmp::max(max_x, 500 + floor_y) + 1;
+        max_y = floor_y - 1;
         let width = (1 + max_x - min_x) as usize;
         let height = (1 + max_y - min_y) as usize;
-        let mut grid: Array2<u8> = Array::zeros((height as usize, width as usize));
+        let mut grid: Array2<u8> = Array::zeros((height, width));
         for x in 0..width {
             for y in 0..height {
                 grid[[y, x]] = b'.';
             }
         }
 
-        if make_floor {
-            for x in 0..width {
-                let r = (floor_y - min_y) as usize;
-                grid[[r, x]] = b'#';
-            }
-        }
-
         // draw lines
         for line in lines {
-            if line.from.x == line.to.x {
-                // vertical
-                let x = (line.from.x - min_x) as usize;
-                let y0 = (cmp::min(line.from.y, line.to.y) - min_y) as usize;
-                let y1 = (cmp::max(line.from.y, line.to.y) + 1 - min_y) as usize;
-                for y in y0..y1 {
-                    grid[[y, x]] = b'#';
-                }
-            }
-            if line.from.y == line.to.y {
-                // vertical
-                let y = (line.from.y - min_y) as usize;
-                let x0 = (cmp::min(line.from.x, line.to.x) - min_x) as usize;
-                let x1 = (cmp::max(line.from.x, line.to.x) + 1 - min_x) as usize;
-                for x in x0..x1 {
-                    grid[[y, x]] = b'#';
-                }
+            for p in line.points() {
+                grid[[(p.y - min_y) as usize, (p.x - min_x) as usize]] = b'#';
             }
         }
 
@@ -295,138 +435,453 @@ impl World {
             state: grid,
             origin: Point { x: min_x, y: min_y },
             input: Point { x: 500, y: 0 },
+            floor: false,
+            path: vec![],
+            particle: Particle::sand(),
         };
     }
 
-    fn add_sand(&mut self) -> SandResult {
-        let offsets = [(0, 1), (-1, 1), (1, 1)];
+    // A grain that only ever moves down is out of the cave after one step per
+    // row, one that can move sideways might also cross every column in a row
+    fn default_step_limit(&self) -> usize {
+        if self.particle.offsets.iter().all(|o| o.1 > 0) {
+            return self.state.nrows();
+        }
+        return self.state.nrows() * self.state.ncols();
+    }
+
+    // each grain settles in its own cell, so there can't be more than cells
+    fn default_grain_limit(&self) -> usize {
+        return self.state.nrows() * self.state.ncols();
+    }
+
+    // summarise the simulation so far, the bounds cover every settled grain
+    fn report(&self, grains: usize, termination: Termination) -> SimulationReport {
+        let mut bounds: Option<(Point, Point)> = None;
+        for ((y, x), v) in self.state.indexed_iter() {
+            if *v != self.particle.glyph {
+                continue;
+            }
+
+            let p = Point {
+                x: x as i32 + self.origin.x,
+                y: y as i32 + self.origin.y,
+            };
+            bounds = match bounds {
+                None => Some((p, p)),
+                Some((min, max)) => Some((
+                    Point {
+                        x: cmp::min(min.x, p.x),
+                        y: cmp::min(min.y, p.y),
+                    },
+                    Point {
+                        x: cmp::max(max.x, p.x),
+                        y: cmp::max(max.y, p.y),
+                    },
+                )),
+            };
+        }
+
+        return SimulationReport {
+            grains: grains,
+            termination: termination,
+            settled_bounds: bounds,
+        };
+    }
+
+    // frames, if any, can be written while the grain is still falling
+    fn add_sand(&mut self, max_steps: usize, mut frames: Option<&mut Frames>) -> SandResult {
         let start_pos = Point {
             x: self.input.x - self.origin.x,
             y: self.input.y - self.origin.y,
         };
-        let mut pos = Point {
-            x: start_pos.x,
-            y: start_pos.y,
-        };
         //println!("Initial position: {:?}", pos);
 
-        if self.state[[start_pos.y as usize, start_pos.x as usize]] == b'o' {
-            return SandResult::Plugged;
+        // every grain follows the last one until just above where it settled
+        if self.path.is_empty() {
+            if self.state[[start_pos.y as usize, start_pos.x as usize]] == self.particle.glyph {
+                return SandResult::Plugged;
+            }
+            self.path.push(start_pos);
         }
+        let mut pos = *self.path.last().unwrap();
 
         // drop sand downward
-        for _ in 0..1000 {
+        for _ in 0..max_steps {
             let mut found_air = false;
-            for offset in offsets {
+            let prev = self.path.len().checked_sub(2).map(|i| self.path[i]);
+            for offset in self.particle.offsets.iter() {
                 let target = Point {
                     x: pos.x + offset.0,
                     y: pos.y + offset.1,
                 };
                 if target.y as usize >= self.state.nrows() {
+                    if self.floor {
+                        // landed on the floor, same as hitting rock
+                        continue;
+                    }
+
                     // done, found a way out of this world
                     //println!("OBLIVION");
                     return SandResult::Oblivion;
                 }
 
-                // should be in bounds, we padded it
-                assert!(target.x >= 0 && (target.x as usize) < self.state.ncols());
-
-                match self.state[[target.y as usize, target.x as usize]] {
-                    b'.' => {
-                        // air, we can move forward
-                        found_air = true;
-                        // println!("{:?} -> {:?}", pos, target);
-                        pos = target;
-                        break;
-                    }
-                    b'#' => {
-                        // rock, keep searching
-                    }
-                    b'+' => {
-                        // starting point, keep searching
-                    }
-                    b'o' => {
-                        // more sand keep searching
-                    }
-                    _ => {
-                        panic!("WTF");
+                // sand can't reach the sides, the grid is sized from the floor
+                // depth, but particles that spread sideways can run off them.
+                // Without a floor they then fall forever, with one they'd
+                // keep going somewhere the grid doesn't cover
+                if target.x < 0 || target.x as usize >= self.state.ncols() {
+                    if self.floor {
+                        return SandResult::OffGrid;
                     }
+                    return SandResult::Oblivion;
+                }
+
+                if Some(target) == prev {
+                    continue;
+                }
+
+                let cell = self.state[[target.y as usize, target.x as usize]];
+                if !self.particle.is_blocked_by(cell) {
+                    // air, we can move forward
+                    found_air = true;
+                    // println!("{:?} -> {:?}", pos, target);
+                    pos = target;
+                    self.path.push(pos);
+                    break;
                 }
             }
 
             if !found_air {
-                self.state[[pos.y as usize, pos.x as usize]] = b'o';
+                self.state[[pos.y as usize, pos.x as usize]] = self.particle.glyph;
+                self.path.pop();
                 return SandResult::Settled;
             }
 
             // otherwise keep falling
+            if let Some(f) = frames.as_deref_mut() {
+                f.grain_moved(self);
+            }
         }
 
         return SandResult::Falling;
     }
-}
 
-fn part1(contents: &str) {
-    let mut world = World::parse(contents, false);
-
-    let mut iters = 0;
-    for _ in 0..1000 {
-        //println!("Add Sand");
-        match world.add_sand() {
-            SandResult::Falling => {
-                panic!("Sand fell forever?");
+    // Binary PPM of the cave, each cell drawn as a scale x scale square. The
+    // path is highlighted, while a grain is falling that's the way it came
+    // and the grain itself is drawn at the end. Between grains it's where the
+    // next one will start falling from
+    fn write_ppm(&self, fname: &str, scale: usize, falling: bool) {
+        let file = fs::File::create(fname).expect("Should be able to create frame");
+        let mut out = std::io::BufWriter::new(file);
+        let (height, width) = (self.state.nrows(), self.state.ncols());
+
+        // the floor gets an extra row of rock at the bottom
+        let image_height = if self.floor { height + 1 } else { height };
+        write!(out, "P6\n{} {}\n255\n", width * scale, image_height * scale).unwrap();
+
+        let mut colors: Array2<[u8; 3]> = Array::from_elem((image_height, width), [128, 128, 128]);
+        for ((y, x), v) in self.state.indexed_iter() {
+            colors[[y, x]] = match *v {
+                b'#' => [128, 128, 128],
+                b'+' => [255, 0, 0],
+                _ if *v == self.particle.glyph => self.particle.color,
+                _ => [16, 16, 32],
+            };
+        }
+        for p in self.path.iter() {
+            if !self
+                .particle
+                .is_blocked_by(self.state[[p.y as usize, p.x as usize]])
+            {
+                colors[[p.y as usize, p.x as usize]] = [255, 255, 0];
             }
-            SandResult::Settled => {
-                // pour more!
+        }
+        if let (true, Some(p)) = (falling, self.path.last()) {
+            colors[[p.y as usize, p.x as usize]] = [255, 255, 255];
+        }
+
+        for row in colors.rows() {
+            for _ in 0..scale {
+                for c in row.iter() {
+                    for _ in 0..scale {
+                        out.write_all(c).unwrap();
+                    }
+                }
             }
-            SandResult::Plugged => {
-                println!("Sand plugged the whole, done");
+        }
+    }
+
+    // Sand ends up in every cell it can reach from the source, going down,
+    // down-left or down-right without passing through rock. So rather than
+    // dropping grains one at a time, fill in each row from the row above.
+    // Only makes sense with a floor, the pile always ends up burying the source.
+    // The floor is just below the last row so never needs checking
+    fn fill(&mut self) -> SimulationReport {
+        let start_pos = Point {
+            x: self.input.x - self.origin.x,
+            y: self.input.y - self.origin.y,
+        };
+
+        let mut count = 0;
+        let mut prev_row: Vec<bool> = vec![false; self.state.ncols()];
+        for y in (start_pos.y as usize)..self.state.nrows() {
+            let mut row: Vec<bool> = vec![false; self.state.ncols()];
+            for x in 0..self.state.ncols() {
+                if self.state[[y, x]] == b'#' {
+                    continue;
+                }
+
+                row[x] = if y == start_pos.y as usize {
+                    x == start_pos.x as usize
+                } else {
+                    prev_row[x]
+                        || (x > 0 && prev_row[x - 1])
+                        || (x + 1 < prev_row.len() && prev_row[x + 1])
+                };
+
+                if row[x] {
+                    self.state[[y, x]] = self.particle.glyph;
+                    count += 1;
+                }
             }
-            SandResult::Oblivion => {
-                println!("Sand left this world, done");
-                break;
+            prev_row = row;
+        }
+        return self.report(count, Termination::Plugged);
+    }
+}
+
+// Writes a frame every few grains, and optionally every few steps of a falling
+// grain, numbered so they sort in order. Turn them into a gif with something
+// like `convert frames/*.ppm sand.gif`
+struct Frames {
+    dir: String,
+    every: usize,
+    every_step: Option<usize>,
+    scale: usize,
+    written: usize,
+    steps: usize, // taken by every grain so far
+}
+
+impl Frames {
+    fn new(dir: &str, every: usize, every_step: Option<usize>, scale: usize) -> Frames {
+        fs::create_dir_all(dir).expect("Should be able to create frame directory");
+        return Frames {
+            dir: dir.to_string(),
+            every: cmp::max(1, every),
+            every_step: every_step.map(|n| cmp::max(1, n)),
+            scale: scale,
+            written: 0,
+            steps: 0,
+        };
+    }
+
+    fn grain_moved(&mut self, world: &World) {
+        self.steps += 1;
+        if let Some(n) = self.every_step {
+            if self.steps.is_multiple_of(n) {
+                self.write(world, true);
             }
         }
-        //println!("{}", world);
-        iters += 1;
     }
 
-    println!("Finished at {}, World: {}", iters, world);
+    fn grain_added(&mut self, world: &World, grains: usize) {
+        if grains.is_multiple_of(self.every) {
+            self.write(world, false);
+        }
+    }
+
+    fn write(&mut self, world: &World, falling: bool) {
+        let fname = format!("{}/frame_{:05}.ppm", self.dir, self.written);
+        world.write_ppm(&fname, self.scale, falling);
+        self.written += 1;
+    }
 }
 
-fn part2(contents: &str) {
-    let mut world = World::parse(contents, true);
+// drop grains one at a time until they stop settling
+fn pour(world: &mut World, limits: Limits, mut frames: Option<&mut Frames>) -> SimulationReport {
+    let max_steps = limits.steps.unwrap_or(world.default_step_limit());
+    let max_grains = limits.grains.unwrap_or(world.default_grain_limit());
 
     let mut iters = 0;
-    for _ in 0..50000 {
+    let mut termination = Termination::GrainLimit;
+    while iters < max_grains {
         //println!("Add Sand");
-        match world.add_sand() {
+        match world.add_sand(max_steps, frames.as_deref_mut()) {
             SandResult::Falling => {
-                panic!("Sand fell forever?");
+                termination = Termination::StepLimit;
+                break;
             }
             SandResult::Settled => {
                 // pour more!
             }
             SandResult::Plugged => {
-                println!("Sand plugged the whole, done");
+                termination = Termination::Plugged;
                 break;
             }
             SandResult::Oblivion => {
-                println!("Sand left this world, done");
+                termination = Termination::Oblivion;
+                break;
+            }
+            SandResult::OffGrid => {
+                termination = Termination::OffGrid;
                 break;
             }
         }
         //println!("{}", world);
         iters += 1;
+
+        if let Some(f) = frames.as_deref_mut() {
+            f.grain_added(world, iters);
+        }
+    }
+
+    // always finish on the final state
+    if let Some(f) = frames {
+        f.write(world, false);
     }
+    return world.report(iters, termination);
+}
+
+// without a floor, sand eventually falls out of the bottom of the cave
+fn part1(world: &World, limits: Limits, frames: Option<&mut Frames>) {
+    let mut world = world.clone();
+    world.floor = false;
 
-    println!("Finished at {}, World: {}", iters, world);
+    let report = pour(&mut world, limits, frames);
+    println!("Part 1: {}", report);
+    println!("Finished at {}, World: {}", report.grains, world);
+}
+
+// with a floor, sand piles up until it buries the source
+fn part2(world: &World, limits: Limits, frames: Option<&mut Frames>) {
+    let mut world = world.clone();
+    world.floor = true;
+
+    // the fill gets the answer directly, but only knows how sand moves. To see
+    // the pile build up we have to drop grains one at a time anyway
+    let use_fill = frames.is_none()
+        && limits.steps.is_none()
+        && limits.grains.is_none()
+        && world.particle.moves_like_sand();
+    let report = if use_fill {
+        world.fill()
+    } else {
+        pour(&mut world, limits, frames)
+    };
+    println!("Part 2: {}", report);
+    println!("Finished at {}, World: {}", report.grains, world);
+}
+
+// report any rock segments that aren't horizontal or vertical, the puzzle
+// only promises straight lines
+fn validate(contents: &str) {
+    let mut count = 0;
+    for line in parse_lines(contents) {
+        if !line.is_axis_aligned() {
+            println!(
+                "Line {}: {},{} -> {},{} is not horizontal or vertical",
+                line.path, line.from.x, line.from.y, line.to.x, line.to.y
+            );
+            count += 1;
+        }
+    }
+    println!("{} diagonal segments", count);
 }
 
 fn main() {
-    let fname = env::args().nth(1).expect("Should pass 1 filename arg");
-    let contents = fs::read_to_string(fname).expect("Should have been able to read the file");
+    let usage = "Usage: <filename> [--part <1|2|both>] [--validate] [--frames <dir>] \
+                 [--every <grains>] [--every-step <steps>] [--scale <pixels>] \
+                 [--particle <sand|sand-right|water>] [--offsets <dx,dy;dx,dy;..>] \
+                 [--blocked <cells>] [--glyph <cell>] [--max-steps <n>] [--max-grains <n>]";
+    let mut fname = None;
+    let mut frames_dir = None;
+    let mut every = 100;
+    let mut every_step = None;
+    let mut scale = 4;
+    let mut check_lines = false;
+    let mut particle = Particle::sand();
+    let mut limits = Limits::default();
+    let mut run_part1 = true;
+    let mut run_part2 = true;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--part" => {
+                (run_part1, run_part2) = match args.next().expect(usage).as_str() {
+                    "1" => (true, false),
+                    "2" => (false, true),
+                    "both" => (true, true),
+                    _ => panic!("{}", usage),
+                };
+            }
+            "--validate" => {
+                check_lines = true;
+            }
+            "--frames" => {
+                frames_dir = Some(args.next().expect(usage));
+            }
+            "--every-step" => {
+                every_step = Some(args.next().expect(usage).parse().expect(usage));
+            }
+            "--every" => {
+                every = args.next().expect(usage).parse().expect(usage);
+            }
+            "--scale" => {
+                scale = args.next().expect(usage).parse().expect(usage);
+            }
+            "--particle" => {
+                particle = Particle::from_name(&args.next().expect(usage)).expect(usage);
+            }
+            "--offsets" => {
+                particle.offsets = args
+                    .next()
+                    .expect(usage)
+                    .split(';')
+                    .map(|pair| {
+                        let (dx, dy) = pair.split_once(',').expect(usage);
+                        return (
+                            dx.trim().parse().expect(usage),
+                            dy.trim().parse().expect(usage),
+                        );
+                    })
+                    .collect();
+            }
+            "--blocked" => {
+                particle.blocked_by = args.next().expect(usage).into_bytes();
+            }
+            "--max-steps" => {
+                limits.steps = Some(args.next().expect(usage).parse().expect(usage));
+            }
+            "--max-grains" => {
+                limits.grains = Some(args.next().expect(usage).parse().expect(usage));
+            }
+            "--glyph" => {
+                particle.glyph = *args.next().expect(usage).as_bytes().first().expect(usage);
+            }
+            _ => {
+                fname = Some(arg);
+            }
+        }
+    }
 
-    //part1(&contents);
-    part2(&contents);
+    let contents =
+        fs::read_to_string(fname.expect(usage)).expect("Should have been able to read the file");
+    if check_lines {
+        validate(&contents);
+        return;
+    }
+
+    let mut world = World::parse(&contents);
+    world.particle = particle;
+
+    // with both parts the frames carry on from where part 1 left off
+    let mut frames = frames_dir.map(|dir| Frames::new(&dir, every, every_step, scale));
+    if run_part1 {
+        part1(&world, limits, frames.as_mut());
+    }
+    if run_part2 {
+        part2(&world, limits, frames.as_mut());
+    }
 }
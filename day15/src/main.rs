@@ -1,8 +1,8 @@
-use ndarray::prelude::*;
 use std::cmp;
 use std::collections::HashSet;
 use std::env;
 use std::fs;
+use std::io::Write;
 
 /*
 --- Day 15: Beacon Exclusion Zone ---
@@ -103,10 +103,10 @@ Consult the report from the sensors you just deployed. In the row where y=200000
 
 
 */
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 struct Point {
-    x: i32,
-    y: i32,
+    x: i64,
+    y: i64,
 }
 
 #[derive(Debug)]
@@ -114,118 +114,341 @@ struct World {
     sensors: Vec<Sensor>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Sensor {
+    id: usize, // position in the input, kept when other sensors are removed
     sensor: Point,
     beacon: Point,
-    dist: i32,
+    dist: i64,
+}
+
+#[derive(Debug)]
+struct ParseError {
+    line: usize, // 1-based
+    text: String,
+    reason: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return write!(f, "line {}: {}: {:?}", self.line, self.reason, self.text);
+    }
 }
 
-fn parse_point(line: &str) -> Point {
-    let (xss, yss) = line.split_once(", ").unwrap();
-    let x: i32 = xss[2..].parse().unwrap();
-    let y: i32 = yss[2..].parse().unwrap();
-    return Point { x: x, y: y };
+// skip any whitespace then the given word
+fn expect<'a>(rest: &'a str, word: &str) -> Result<&'a str, String> {
+    return match rest.trim_start().strip_prefix(word) {
+        Some(rest) => Ok(rest),
+        None => Err(format!("expected '{}'", word)),
+    };
 }
 
-fn parse_line(line: &str) -> Sensor {
-    let (sensor_str, beacon_str) = line.split_once(":").unwrap();
-    let sensor_pt = parse_point(&sensor_str[10..]);
-    let beacon_pt = parse_point(&beacon_str[22..]);
-    return Sensor {
-        dist: ((&sensor_pt.x - &beacon_pt.x).abs() + (&sensor_pt.y - &beacon_pt.y).abs()),
+fn parse_number(rest: &str) -> Result<(i64, &str), String> {
+    let rest = rest.trim_start();
+    let mut len = 0;
+    for (i, c) in rest.char_indices() {
+        if c.is_ascii_digit() || (i == 0 && (c == '-' || c == '+')) {
+            len = i + 1;
+        } else {
+            break;
+        }
+    }
+
+    let number = rest[..len]
+        .parse()
+        .map_err(|_| "expected a number".to_string())?;
+    return Ok((number, &rest[len..]));
+}
+
+// x=.., y=..
+fn parse_point(rest: &str) -> Result<(Point, &str), String> {
+    let rest = expect(rest, "x")?;
+    let rest = expect(rest, "=")?;
+    let (x, rest) = parse_number(rest)?;
+    let rest = expect(rest, ",")?;
+    let rest = expect(rest, "y")?;
+    let rest = expect(rest, "=")?;
+    let (y, rest) = parse_number(rest)?;
+    return Ok((Point { x: x, y: y }, rest));
+}
+
+// Sensor at x=.., y=..: closest beacon is at x=.., y=..
+fn parse_line(line: &str) -> Result<Sensor, String> {
+    let mut rest = line;
+    for word in ["Sensor", "at"] {
+        rest = expect(rest, word)?;
+    }
+    let (sensor_pt, mut rest) = parse_point(rest)?;
+    for word in [":", "closest", "beacon", "is", "at"] {
+        rest = expect(rest, word)?;
+    }
+    let (beacon_pt, rest) = parse_point(rest)?;
+    if !rest.trim().is_empty() {
+        return Err(format!("unexpected '{}' after beacon", rest.trim()));
+    }
+
+    let dx = sensor_pt
+        .x
+        .checked_sub(beacon_pt.x)
+        .and_then(|d| d.checked_abs());
+    let dy = sensor_pt
+        .y
+        .checked_sub(beacon_pt.y)
+        .and_then(|d| d.checked_abs());
+    let dist = match dx.zip(dy).and_then(|(dx, dy)| dx.checked_add(dy)) {
+        Some(dist) => dist,
+        None => return Err("sensor to beacon distance overflows".to_string()),
+    };
+    // World::parse knows where the line is, so it fills in the id
+    return Ok(Sensor {
+        id: 0,
+        dist: dist,
         sensor: sensor_pt,
         beacon: beacon_pt,
+    });
+}
+
+// a window onto the world, each output cell covers scale x scale positions
+#[derive(Debug)]
+struct Viewport {
+    min_x: i64,
+    min_y: i64,
+    max_x: i64, // inclusive
+    max_y: i64, // inclusive
+    scale: i64,
+}
+
+impl Viewport {
+    // pick a scale so the whole window fits in max_cells columns and rows
+    fn fit(min_x: i64, min_y: i64, max_x: i64, max_y: i64, max_cells: i64) -> Viewport {
+        let span = cmp::max(max_x - min_x, max_y - min_y) / max_cells + 1;
+        return Viewport {
+            min_x: min_x,
+            min_y: min_y,
+            max_x: max_x,
+            max_y: max_y,
+            scale: cmp::max(1, span),
+        };
+    }
+
+    fn width(&self) -> usize {
+        return ((self.max_x - self.min_x) / self.scale + 1) as usize;
+    }
+
+    fn height(&self) -> usize {
+        return ((self.max_y - self.min_y) / self.scale + 1) as usize;
+    }
+
+    // inclusive range of positions covered by cell i along one axis
+    fn cell_span(&self, min: i64, max: i64, i: usize) -> (i64, i64) {
+        let lo = min + (i as i64) * self.scale;
+        return (lo, cmp::min(max, lo + self.scale - 1));
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Cell {
+    Sensor,
+    Beacon,
+    Coverage(f64), // fraction of positions in the cell that are covered
+}
+
+// fully covered cells are #, partly covered +
+fn cell_char(cell: &Cell) -> char {
+    return match cell {
+        Cell::Sensor => 'S',
+        Cell::Beacon => 'B',
+        Cell::Coverage(c) if *c >= 1.0 => '#',
+        Cell::Coverage(c) if *c <= 0.0 => '.',
+        Cell::Coverage(_) => '+',
     };
 }
 
+#[derive(Debug, Clone, Copy)]
+enum ImageFormat {
+    Pgm,
+    Ppm,
+}
+
 impl std::fmt::Display for World {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let mut min_x = i32::MAX;
-        let mut max_x = i32::MIN;
-        let mut min_y = i32::MAX;
-        let mut max_y = i32::MIN;
-        let mut max_d = 0;
-        for sensor in self.sensors.iter() {
-            let s = &sensor.sensor;
-            let b = &sensor.beacon;
+        let (min_x, min_y, max_x, max_y) = self.bounds();
+        let view = Viewport::fit(min_x, min_y, max_x, max_y, 100);
+        for y in 0..view.height() {
+            for cell in self.render_row(&view, y) {
+                write!(f, "{}", cell_char(&cell))?;
+            }
+            writeln!(f)?;
+        }
+        return Ok(());
+    }
+}
 
-            min_x = cmp::min(b.x, min_x);
-            min_x = cmp::min(s.x, min_x);
+#[derive(Debug)]
+pub struct Range {
+    min_x: i64,
+    max_x: i64,
+}
 
-            max_x = cmp::max(b.x, max_x);
-            max_x = cmp::max(s.x, max_x);
+impl Range {
+    fn len(&self) -> usize {
+        return (self.max_x - self.min_x + 1) as usize;
+    }
 
-            min_y = cmp::min(b.y, min_y);
-            min_y = cmp::min(s.y, min_y);
+    fn contains(&self, x: i64) -> bool {
+        return self.min_x <= x && x <= self.max_x;
+    }
+}
 
-            max_y = cmp::max(b.y, max_y);
-            max_y = cmp::max(s.y, max_y);
+impl World {
+    fn parse(contents: &str) -> Result<World, ParseError> {
+        let mut sensors: Vec<Sensor> = vec![];
+        for (i, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match parse_line(line) {
+                Ok(sensor) => sensors.push(Sensor {
+                    id: sensors.len(),
+                    ..sensor
+                }),
+                Err(reason) => {
+                    return Err(ParseError {
+                        line: i + 1,
+                        text: line.to_string(),
+                        reason: reason,
+                    });
+                }
+            }
+        }
+        return Ok(World { sensors: sensors });
+    }
 
-            max_d = cmp::max(max_d, sensor.dist);
+    // smallest box holding every sensor's coverage
+    fn bounds(&self) -> (i64, i64, i64, i64) {
+        let mut min_x = i64::MAX;
+        let mut max_x = i64::MIN;
+        let mut min_y = i64::MAX;
+        let mut max_y = i64::MIN;
+        for sensor in self.sensors.iter() {
+            let s = &sensor.sensor;
+            min_x = cmp::min(s.x - sensor.dist, min_x);
+            max_x = cmp::max(s.x + sensor.dist, max_x);
+            min_y = cmp::min(s.y - sensor.dist, min_y);
+            max_y = cmp::max(s.y + sensor.dist, max_y);
         }
+        return (min_x, min_y, max_x, max_y);
+    }
 
-        min_x -= max_d;
-        min_y -= max_d;
+    // Compute a single row of output cells. Coverage is worked out from the
+    // merged ranges of each row the cell spans, so nothing is allocated for
+    // positions that aren't being drawn
+    fn render_row(&self, view: &Viewport, row: usize) -> Vec<Cell> {
+        let width = view.width();
+        let (y0, y1) = view.cell_span(view.min_y, view.max_y, row);
+
+        // cells strictly between a range's first and last cell are fully
+        // covered, those are tallied as a running difference and summed at the end
+        let mut covered: Vec<i64> = vec![0; width];
+        let mut full: Vec<i64> = vec![0; width + 1];
+        for y in y0..=y1 {
+            for r in self.covered_ranges(y) {
+                let lo = cmp::max(r.min_x, view.min_x);
+                let hi = cmp::min(r.max_x, view.max_x);
+                if lo > hi {
+                    continue;
+                }
 
-        let width = (2 * max_d + 1 + max_x - min_x) as usize;
-        let height = (2 * max_d + 1 + max_y - min_y) as usize;
-        let mut grid: Array2<u8> = Array::zeros((height as usize, width as usize));
-        for x in 0..width {
-            for y in 0..height {
-                grid[[y, x]] = b'.';
+                let first = ((lo - view.min_x) / view.scale) as usize;
+                let last = ((hi - view.min_x) / view.scale) as usize;
+                if first == last {
+                    covered[first] += hi - lo + 1;
+                    continue;
+                }
+
+                let (_, first_end) = view.cell_span(view.min_x, view.max_x, first);
+                let (last_start, _) = view.cell_span(view.min_x, view.max_x, last);
+                covered[first] += first_end - lo + 1;
+                covered[last] += hi - last_start + 1;
+                full[first + 1] += view.scale;
+                full[last] -= view.scale;
             }
         }
-        // draw sensor and beacons
-        // and render the areas that are covered
+
+        let mut running = 0;
+        for (i, count) in covered.iter_mut().enumerate() {
+            running += full[i];
+            *count += running;
+        }
+
+        let mut cells: Vec<Cell> = vec![];
+        for (i, count) in covered.iter().enumerate() {
+            let (x0, x1) = view.cell_span(view.min_x, view.max_x, i);
+            let area = (x1 - x0 + 1) * (y1 - y0 + 1);
+            cells.push(Cell::Coverage(*count as f64 / area as f64));
+        }
+
+        // sensors and beacons are drawn over the coverage
         for sensor in self.sensors.iter() {
-            for dx in -sensor.dist..(sensor.dist + 1) {
-                // ydist + dx
-                let ydist = sensor.dist - dx.abs();
-                for dy in -ydist..(ydist + 1) {
-                    let x = dx + sensor.sensor.x - min_x;
-                    let y = dy + sensor.sensor.y - min_y;
-                    grid[[y as usize, x as usize]] = b'#';
+            for (p, cell) in [
+                (&sensor.beacon, Cell::Beacon),
+                (&sensor.sensor, Cell::Sensor),
+            ] {
+                if p.x < view.min_x || p.x > view.max_x || p.y < y0 || p.y > y1 {
+                    continue;
                 }
+                cells[((p.x - view.min_x) / view.scale) as usize] = cell;
             }
-            let sx = sensor.sensor.x - min_x;
-            let sy = sensor.sensor.y - min_y;
-            let bx = sensor.beacon.x - min_x;
-            let by = sensor.beacon.y - min_y;
-            grid[[sy as usize, sx as usize]] = b'S';
-            grid[[by as usize, bx as usize]] = b'B';
         }
+        return cells;
+    }
 
-        for row in grid.rows() {
-            for v in row {
-                write!(f, "{}", *v as char).unwrap();
+    // binary PGM (greyscale) or PPM (colour) image, covered positions are white
+    // and uncovered black. In colour sensors are red and beacons blue
+    fn write_image(&self, view: &Viewport, fname: &str, format: ImageFormat) {
+        let file = fs::File::create(fname).expect("Should be able to create image");
+        let mut out = std::io::BufWriter::new(file);
+        let magic = match format {
+            ImageFormat::Pgm => "P5",
+            ImageFormat::Ppm => "P6",
+        };
+        write!(out, "{}\n{} {}\n255\n", magic, view.width(), view.height()).unwrap();
+
+        for y in 0..view.height() {
+            for cell in self.render_row(view, y) {
+                let rgb: [u8; 3] = match cell {
+                    Cell::Sensor => [255, 0, 0],
+                    Cell::Beacon => [0, 0, 255],
+                    Cell::Coverage(c) => {
+                        let v = (c * 255.0).round() as u8;
+                        [v, v, v]
+                    }
+                };
+                match format {
+                    ImageFormat::Pgm => out.write_all(&rgb[1..2]).unwrap(),
+                    ImageFormat::Ppm => out.write_all(&rgb).unwrap(),
+                }
             }
-            write!(f, "\n").unwrap();
         }
-        return Ok(());
     }
-}
-
-#[derive(Debug)]
-struct Range {
-    min_x: i32,
-    max_x: i32,
-}
 
-impl World {
-    fn parse(contents: &str, make_floor: bool) -> World {
-        // read lines and compute bounds
-        let mut sensors: Vec<Sensor> = vec![];
-        for line in contents.lines() {
-            sensors.push(parse_line(line));
+    // the example only spans a few dozen positions, real inputs span millions
+    fn is_small(&self) -> bool {
+        for sensor in self.sensors.iter() {
+            for p in [&sensor.sensor, &sensor.beacon] {
+                if p.x.abs() > 1000 || p.y.abs() > 1000 {
+                    return false;
+                }
+            }
         }
-        return World { sensors: sensors };
+        return true;
     }
 
-    fn count_row_coverage(&self, row_of_interest: i32) -> usize {
-        // draw sensor and beacons
-        // and render the areas that are covered
-        let mut coverage = HashSet::<i32>::new();
+    // merged, sorted list of x ranges (inclusive) covered by any sensor on the
+    // given row. Beacons are not removed, a beacon's own position is covered
+    pub fn covered_ranges(&self, row_of_interest: i64) -> Vec<Range> {
+        let mut ranges: Vec<Range> = vec![];
         for sensor in self.sensors.iter() {
             let row_dist = (sensor.sensor.y - row_of_interest).abs();
             if row_dist > sensor.dist {
@@ -234,94 +457,489 @@ impl World {
             }
 
             let xdist = sensor.dist - row_dist;
-            for dx in -xdist..(xdist + 1) {
-                let x = dx + sensor.sensor.x;
-                coverage.insert(x);
+            ranges.push(Range {
+                min_x: sensor.sensor.x - xdist,
+                max_x: sensor.sensor.x + xdist,
+            });
+        }
+
+        ranges.sort_by(|a, b| a.min_x.partial_cmp(&b.min_x).unwrap());
+
+        // in increasing order, grow the last range while the next one overlaps
+        // or touches it
+        let mut merged: Vec<Range> = vec![];
+        for r in ranges {
+            match merged.last_mut() {
+                Some(last) if r.min_x <= last.max_x.saturating_add(1) => {
+                    last.max_x = cmp::max(last.max_x, r.max_x);
+                }
+                _ => merged.push(r),
             }
         }
+        return merged;
+    }
+
+    fn count_row_coverage(&self, row_of_interest: i64) -> usize {
+        let ranges = self.covered_ranges(row_of_interest);
+        let mut covered: usize = ranges.iter().map(|r| r.len()).sum();
 
         // drop beacon locations
         // stupid, they didn't really specify this
+        let mut beacons = HashSet::<i64>::new();
         for sensor in self.sensors.iter() {
             if sensor.beacon.y == row_of_interest {
-                coverage.remove(&sensor.beacon.x);
+                beacons.insert(sensor.beacon.x);
+            }
+        }
+        for x in beacons {
+            if ranges.iter().any(|r| r.contains(x)) {
+                covered -= 1;
             }
         }
 
-        return coverage.len();
+        return covered;
     }
 
-    fn find_gap(&self, row_of_interest: i32, min_x: i32, max_x: i32) -> Option<i32> {
-        let mut ranges: Vec<Range> = vec![];
+    fn is_covered(&self, p: &Point) -> bool {
         for sensor in self.sensors.iter() {
-            let row_dist = (sensor.sensor.y - row_of_interest).abs();
-            if row_dist > sensor.dist {
-                // too far
-                continue;
+            let d = (sensor.sensor.x - p.x).abs() + (sensor.sensor.y - p.y).abs();
+            if d <= sensor.dist {
+                return true;
             }
+        }
+        return false;
+    }
 
-            let xdist = sensor.dist - row_dist;
-            ranges.push(Range {
-                min_x: sensor.sensor.x - xdist,
-                max_x: sensor.sensor.x + xdist,
-            });
+    // every sensor that covers p, along with its slack: how much
+    // further than p the sensor can still see
+    pub fn covering_sensors(&self, p: &Point) -> Vec<(&Sensor, i64)> {
+        let mut out: Vec<(&Sensor, i64)> = vec![];
+        for sensor in self.sensors.iter() {
+            let d = (sensor.sensor.x - p.x).abs() + (sensor.sensor.y - p.y).abs();
+            if d <= sensor.dist {
+                out.push((sensor, sensor.dist - d));
+            }
         }
+        return out;
+    }
 
-        ranges.sort_by(|a, b| a.min_x.partial_cmp(&b.min_x).unwrap());
+    // number of positions in the box (inclusive) covered by at least one sensor
+    pub fn covered_area(&self, min: &Point, max: &Point) -> i64 {
+        let mut area = 0;
+        for y in min.y..=max.y {
+            for r in self.covered_ranges(y) {
+                let lo = cmp::max(r.min_x, min.x);
+                let hi = cmp::min(r.max_x, max.x);
+                if lo <= hi {
+                    area += hi - lo + 1;
+                }
+            }
+        }
+        return area;
+    }
+
+    // positions in the box (inclusive) no sensor covers, row by row, stopping
+    // once limit have been found
+    pub fn uncovered_points(&self, min: &Point, max: &Point, limit: usize) -> Vec<Point> {
+        let mut out: Vec<Point> = vec![];
+        for y in min.y..=max.y {
+            let mut x = min.x; // x is the first position not yet checked
+            let ranges = self.covered_ranges(y);
+            for r in ranges.iter() {
+                while x < r.min_x && x <= max.x {
+                    if out.len() == limit {
+                        return out;
+                    }
+                    out.push(Point { x: x, y: y });
+                    x += 1;
+                }
+                x = cmp::max(x, r.max_x.saturating_add(1));
+            }
+            while x <= max.x {
+                if out.len() == limit {
+                    return out;
+                }
+                out.push(Point { x: x, y: y });
+                x += 1;
+            }
+        }
+        return out;
+    }
+
+    // same world with some sensors dropped, indices refer to input order
+    pub fn without_sensors(&self, removed: &[usize]) -> World {
+        let mut sensors: Vec<Sensor> = vec![];
+        for sensor in self.sensors.iter() {
+            if !removed.contains(&sensor.id) {
+                sensors.push(sensor.clone());
+            }
+        }
+        return World { sensors: sensors };
+    }
 
-        //println!("Ranges: {:?}", ranges);
+    // If only one point in the square is uncovered it has to sit just outside
+    // the diamond of some sensors, hemmed in on every side. Each diamond is
+    // bounded by lines x + y = a and x - y = b, so the point lies where an a
+    // line one step outside one sensor crosses a b line one step outside
+    // another, or right next to where they cross. Along the edge of the square
+    // the edge itself can do the hemming in, so also try where each line meets
+    // an edge, and the corners.
+    fn find_uncovered(&self, min: i64, max: i64) -> Option<Point> {
+        let mut a_lines: Vec<i64> = vec![];
+        let mut b_lines: Vec<i64> = vec![];
+        for sensor in self.sensors.iter() {
+            let s = &sensor.sensor;
+            a_lines.push(s.x + s.y - sensor.dist - 1);
+            a_lines.push(s.x + s.y + sensor.dist + 1);
+            b_lines.push(s.x - s.y - sensor.dist - 1);
+            b_lines.push(s.x - s.y + sensor.dist + 1);
+        }
+
+        let mut candidates = vec![
+            Point { x: min, y: min },
+            Point { x: min, y: max },
+            Point { x: max, y: min },
+            Point { x: max, y: max },
+        ];
+        for a in a_lines.iter() {
+            candidates.push(Point { x: min, y: a - min });
+            candidates.push(Point { x: max, y: a - max });
+            candidates.push(Point { x: a - min, y: min });
+            candidates.push(Point { x: a - max, y: max });
+        }
+        for b in b_lines.iter() {
+            candidates.push(Point { x: min, y: min - b });
+            candidates.push(Point { x: max, y: max - b });
+            candidates.push(Point { x: b + min, y: min });
+            candidates.push(Point { x: b + max, y: max });
+        }
+        for a in a_lines.iter() {
+            for b in b_lines.iter() {
+                // lines only cross on a whole number when a and b share parity
+                if (a - b) % 2 == 0 {
+                    candidates.push(Point {
+                        x: (a + b) / 2,
+                        y: (a - b) / 2,
+                    });
+                    continue;
+                }
+
+                // otherwise they pass between points, which happens when a
+                // sensor two steps away closes off a gap one wide. The point
+                // is then next to the crossing, on one line or the other
+                for (a, b) in [(a + 1, *b), (a - 1, *b), (*a, b + 1), (*a, b - 1)] {
+                    candidates.push(Point {
+                        x: (a + b) / 2,
+                        y: (a - b) / 2,
+                    });
+                }
+            }
+        }
+
+        for p in candidates {
+            if p.x < min || p.x > max || p.y < min || p.y > max {
+                continue;
+            }
+            if !self.is_covered(&p) {
+                return Some(p);
+            }
+        }
+        return None;
+    }
+
+    fn find_gap(&self, row_of_interest: i64, min_x: i64, max_x: i64) -> Option<i64> {
         // in increasing order check coverage
         let mut limit = min_x; // limit is uncovered
-        for r in ranges {
+        for r in self.covered_ranges(row_of_interest) {
             if r.min_x > limit {
                 // found a gap
-                return Some(limit);
+                break;
             }
-            limit = cmp::max(limit, r.max_x + 1);
+            limit = cmp::max(limit, r.max_x.saturating_add(1));
         }
 
+        if limit <= max_x {
+            return Some(limit);
+        }
         return None;
     }
 }
 
-fn part1(contents: &str) {
-    let world = World::parse(contents, false);
-    println!(
-        "Row {} coverage: {}",
-        2000000,
-        world.count_row_coverage(2000000)
-    );
+fn part1(world: &World, row: i64) {
+    println!("Row {} coverage: {}", row, world.count_row_coverage(row));
 }
 
-fn part2(contents: &str) {
-    let world = World::parse(contents, true);
+// widened so points far outside the puzzle's square can't overflow
+fn tuning_frequency(p: &Point) -> i128 {
+    return (p.x as i128) * 4000000 + (p.y as i128);
+}
 
-    //for row in 0..20 {
-    for row in 0..4000000 {
+// the old way, check every row for a gap
+fn scan_rows(world: &World, min: i64, max: i64) -> Option<Point> {
+    for row in min..=max {
         if row % 10000 == 0 {
             println!("Searching row: {}", row);
         }
-        match world.find_gap(row, 0, 4000000) {
-            //match world.find_gap(row, 0, 20) {
-            None => {}
-            Some(x) => {
+        if let Some(x) = world.find_gap(row, min, max) {
+            return Some(Point { x: x, y: row });
+        }
+    }
+    return None;
+}
+
+fn part2(world: &World, min: i64, max: i64, cross_check: bool) {
+    let found = world.find_uncovered(min, max);
+    match found {
+        None => {
+            println!("No gap found");
+        }
+        Some(p) => {
+            println!(
+                "Found gap at {}, {}, signal: {}",
+                p.x,
+                p.y,
+                tuning_frequency(&p)
+            );
+        }
+    }
+
+    if cross_check {
+        let scanned = scan_rows(world, min, max);
+        println!("Row scan found: {:?}", scanned);
+        assert!(scanned == found, "Row scan disagrees");
+    }
+    //println!("{}", world);
+}
+
+fn parse_arg(arg: Option<String>, usage: &str) -> i64 {
+    return arg.expect(usage).trim().parse().expect(usage);
+}
+
+// comma separated numbers, len is how many there must be (if any)
+fn parse_list(arg: Option<String>, len: Option<usize>, usage: &str) -> Vec<i64> {
+    let values = arg
+        .expect(usage)
+        .split(',')
+        .map(|v| parse_arg(Some(v.to_string()), usage))
+        .collect::<Vec<i64>>();
+    if let Some(len) = len {
+        assert!(values.len() == len, "{}", usage);
+    }
+    return values;
+}
+
+fn main() {
+    let usage = "Usage: <filename> [--row <y>] [--min <xy>] [--max <xy>] [--rows <y,..>] \
+                 [--scan] [--view <min_x,min_y,max_x,max_y>] [--scale <n>] [--image <out>] \
+                 [--point <x,y>] [--box <min_x,min_y,max_x,max_y>] [--limit <n>] \
+                 [--remove <i,..>]";
+    let mut fname = None;
+    let mut row = None;
+    let mut min = 0;
+    let mut max = None;
+    let mut rows: Vec<i64> = vec![];
+    let mut cross_check = false; // also run the slow row by row search
+    let mut view: Option<Vec<i64>> = None;
+    let mut scale = None;
+    let mut image = None;
+    let mut point: Option<Vec<i64>> = None;
+    let mut query_box: Option<Vec<i64>> = None;
+    let mut limit = 10;
+    let mut removed: Vec<usize> = vec![];
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--row" => {
+                row = Some(parse_arg(args.next(), usage));
+            }
+            "--min" => {
+                min = parse_arg(args.next(), usage);
+            }
+            "--max" => {
+                max = Some(parse_arg(args.next(), usage));
+            }
+            "--rows" => {
+                rows = parse_list(args.next(), None, usage);
+            }
+            "--scan" => {
+                cross_check = true;
+            }
+            "--view" => {
+                let v = parse_list(args.next(), Some(4), usage);
+                assert!(v[0] <= v[2] && v[1] <= v[3], "{}", usage);
+                view = Some(v);
+            }
+            "--scale" => {
+                scale = Some(parse_arg(args.next(), usage));
+            }
+            "--image" => {
+                image = args.next();
+            }
+            "--point" => {
+                point = Some(parse_list(args.next(), Some(2), usage));
+            }
+            "--box" => {
+                query_box = Some(parse_list(args.next(), Some(4), usage));
+            }
+            "--limit" => {
+                limit = parse_arg(args.next(), usage) as usize;
+            }
+            "--remove" => {
+                removed = parse_list(args.next(), None, usage)
+                    .iter()
+                    .map(|i| *i as usize)
+                    .collect();
+            }
+            _ => {
+                fname = Some(arg);
+            }
+        }
+    }
+
+    let contents =
+        fs::read_to_string(fname.expect(usage)).expect("Should have been able to read the file");
+    let mut world = World::parse(&contents).unwrap_or_else(|e| panic!("{}", e));
+    if !removed.is_empty() {
+        world = world.without_sensors(&removed);
+    }
+
+    if let Some(p) = point {
+        let p = Point { x: p[0], y: p[1] };
+        let covering = world.covering_sensors(&p);
+        println!("{:?} is covered by {} sensors", p, covering.len());
+        for (sensor, slack) in covering {
+            println!(
+                "    sensor {} at {:?}, beacon at {:?}, slack {}",
+                sensor.id, sensor.sensor, sensor.beacon, slack
+            );
+        }
+        return;
+    }
+
+    if let Some(b) = query_box {
+        let (min, max) = (Point { x: b[0], y: b[1] }, Point { x: b[2], y: b[3] });
+        let total = (max.x - min.x + 1) * (max.y - min.y + 1);
+        println!(
+            "Covered area: {} of {}",
+            world.covered_area(&min, &max),
+            total
+        );
+
+        let uncovered = world.uncovered_points(&min, &max, limit);
+        println!("Uncovered (up to {}): {:?}", limit, uncovered);
+        return;
+    }
+
+    if view.is_some() || image.is_some() {
+        let (min_x, min_y, max_x, max_y) = match view {
+            Some(v) => (v[0], v[1], v[2], v[3]),
+            None => world.bounds(),
+        };
+        // images can hold a lot more detail than a terminal
+        let max_cells = if image.is_some() { 1000 } else { 100 };
+        let mut viewport = Viewport::fit(min_x, min_y, max_x, max_y, max_cells);
+        if let Some(s) = scale {
+            viewport.scale = cmp::max(1, s);
+        }
+
+        match image {
+            Some(fname) => {
+                let format = if fname.ends_with(".pgm") {
+                    ImageFormat::Pgm
+                } else {
+                    ImageFormat::Ppm
+                };
+                world.write_image(&viewport, &fname, format);
                 println!(
-                    "Found gap at {}, {}, signal: {}",
-                    x,
-                    row,
-                    (x as i64) * 4000000 + (row as i64)
+                    "Wrote {}x{} image to {}",
+                    viewport.width(),
+                    viewport.height(),
+                    fname
                 );
-                break;
+            }
+            None => {
+                for y in 0..viewport.height() {
+                    let line = world
+                        .render_row(&viewport, y)
+                        .iter()
+                        .map(cell_char)
+                        .collect::<String>();
+                    println!("{}", line);
+                }
             }
         }
+        return;
     }
-    //println!("{}", world);
+
+    if !rows.is_empty() {
+        for y in rows {
+            let ranges = world.covered_ranges(y);
+            println!(
+                "Row {} coverage: {}, ranges: {:?}",
+                y,
+                world.count_row_coverage(y),
+                ranges
+                    .iter()
+                    .map(|r| (r.min_x, r.max_x))
+                    .collect::<Vec<(i64, i64)>>()
+            );
+        }
+        return;
+    }
+
+    // default to the puzzle's numbers, which differ for the example
+    let (default_row, default_max) = if world.is_small() {
+        (10, 20)
+    } else {
+        (2000000, 4000000)
+    };
+
+    part1(&world, row.unwrap_or(default_row));
+    part2(&world, min, max.unwrap_or(default_max), cross_check);
 }
 
-fn main() {
-    let fname = env::args().nth(1).expect("Should pass 1 filename arg");
-    let contents = fs::read_to_string(fname).expect("Should have been able to read the file");
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the only uncovered point is on the left edge, between two boundary
+    // lines that run the same way, so no pair of lines crosses there
+    #[test]
+    fn uncovered_point_on_edge() {
+        let world = World::parse(
+            "Sensor at x=11, y=8: closest beacon is at x=14, y=14
+Sensor at x=-3, y=-1: closest beacon is at x=2, y=-3
+Sensor at x=2, y=-3: closest beacon is at x=7, y=-1
+Sensor at x=8, y=6: closest beacon is at x=10, y=2
+Sensor at x=-3, y=12: closest beacon is at x=-1, y=8
+Sensor at x=8, y=8: closest beacon is at x=13, y=14
+",
+        )
+        .unwrap();
+
+        let expected = Some(Point { x: 0, y: 4 });
+        assert_eq!(scan_rows(&world, 0, 9), expected);
+        assert_eq!(world.find_uncovered(0, 9), expected);
+    }
 
-    part1(&contents);
-    part2(&contents);
+    // the uncovered point is closed off by a sensor two steps away, so the
+    // nearest boundary lines cross half a step from it
+    #[test]
+    fn uncovered_point_next_to_crossing() {
+        let world = World::parse(
+            "Sensor at x=9, y=10: closest beacon is at x=3, y=11
+Sensor at x=9, y=1: closest beacon is at x=7, y=0
+Sensor at x=1, y=12: closest beacon is at x=4, y=14
+Sensor at x=3, y=-2: closest beacon is at x=6, y=4
+Sensor at x=12, y=4: closest beacon is at x=8, y=3
+Sensor at x=-2, y=8: closest beacon is at x=4, y=5
+",
+        )
+        .unwrap();
+
+        let expected = Some(Point { x: 6, y: 5 });
+        assert_eq!(scan_rows(&world, 0, 9), expected);
+        assert_eq!(world.find_uncovered(0, 9), expected);
+    }
 }
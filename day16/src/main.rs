@@ -1,6 +1,11 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
 use ndarray::prelude::*;
+use std::cmp;
+use std::collections::HashMap;
 use std::collections::VecDeque;
 use std::env;
+use std::fmt::Write;
 use std::fs;
 
 /*--- Day 16: Proboscidea Volcanium ---
@@ -168,6 +173,21 @@ This approach lets you release the most pressure possible in 30 minutes with thi
 Work out the steps to release the most pressure in 30 minutes. What is the most pressure you can
 release?
 
+--- Part Two ---
+You're worried that even with an optimal approach, the pressure released won't be enough. What if
+you got one of the elephants to help you?
+
+It would take you 4 minutes to teach an elephant how to open the right valves in the right order,
+leaving you with only 26 minutes to actually execute your plan. Would having two of you working
+together be better, even if it means having less time? (Assume that you teach the elephant before
+opening any valves yourself, giving you both the same full 26 minutes.)
+
+In the example above, you and the elephant could open JJ, BB and CC (you) and DD, HH and EE (the
+elephant). With the elephant helping, after 26 minutes, the best you could do would release a total
+of 1707 pressure.
+
+With you and an elephant working together for 26 minutes, what is the most pressure you could
+release?
 
 */
 
@@ -178,6 +198,7 @@ struct World {
     timeout: usize,
 
     dists: Array2<usize>,
+    next: Array2<usize>, // next[[u, v]] is the first hop on a shortest path from u to v
 
     // order to take neighbors during traversal
     // this is a plan, its not updatd during traversal
@@ -187,13 +208,17 @@ struct World {
 #[derive(Debug)]
 struct Valve {
     name: String,
-    rate: i32,
+    rate: usize,
     neighbors: Vec<usize>, // indices into valves array
 }
 
+// marks pairs of valves with no path between them, in both the distance and
+// next-hop matrices
+const UNREACHABLE: usize = usize::MAX;
+
 fn get_path(next: &Array2<usize>, u_in: usize, v: usize) -> VecDeque<usize> {
     let mut u = u_in;
-    if next[[u, v]] == usize::MAX {
+    if next[[u, v]] == UNREACHABLE {
         println!("No path!");
         return VecDeque::new();
     }
@@ -206,153 +231,319 @@ fn get_path(next: &Array2<usize>, u_in: usize, v: usize) -> VecDeque<usize> {
     return path;
 }
 
-fn compute_dists(valves: &Vec<Valve>) -> Array2<usize> {
+// returns the distance matrix and the next-hop matrix used by get_path
+fn compute_dists(valves: &[Valve]) -> (Array2<usize>, Array2<usize>) {
     // https://en.wikipedia.org/wiki/Floyd%E2%80%93Warshall_algorithm
     let n = valves.len();
     let mut dist = Array::zeros((n, n));
-    dist.fill(valves.len());
+    dist.fill(UNREACHABLE);
+    let mut next = Array::zeros((n, n));
+    next.fill(UNREACHABLE);
 
     for (u, valve) in valves.iter().enumerate() {
         for nbr in valve.neighbors.iter() {
             // can go directly, dist = 1, next equals direct
             dist[[u, *nbr]] = 1;
+            next[[u, *nbr]] = *nbr;
         }
     }
 
-    for (u, valve) in valves.iter().enumerate() {
-        for _ in valve.neighbors.iter() {
-            // already here, dist is zero
-            dist[[u, u]] = 0;
-        }
+    for u in 0..n {
+        // already here, dist is zero
+        dist[[u, u]] = 0;
+        next[[u, u]] = u;
     }
 
     for k in 0..n {
         for i in 0..n {
             for j in 0..n {
                 // if going through k makes the distance shorter, then take it
+                if dist[[i, k]] == UNREACHABLE || dist[[k, j]] == UNREACHABLE {
+                    continue;
+                }
                 if dist[[i, j]] > dist[[i, k]] + dist[[k, j]] {
                     dist[[i, j]] = dist[[i, k]] + dist[[k, j]];
+                    next[[i, j]] = next[[i, k]];
                 }
             }
         }
     }
 
-    return dist;
+    return (dist, next);
 }
 
 impl std::fmt::Display for World {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         for v in self.valves.iter() {
-            write!(
-                f,
-                "Valve {} has flow rate={}; tunnels lead to ",
-                v.name, v.rate
-            )
-            .unwrap();
+            write!(f, "Valve {} has flow rate={}; ", v.name, v.rate)?;
 
             if v.neighbors.len() > 1 {
-                write!(f, "valves ",).unwrap();
+                write!(f, "tunnels lead to valves ")?;
             } else {
-                write!(f, "valve ").unwrap();
-            }
-
-            for n in v.neighbors.iter() {
-                write!(f, "{}, ", self.valves[*n].name).unwrap();
+                write!(f, "tunnel leads to valve ")?;
             }
 
-            write!(f, "\n").unwrap();
+            let names = v
+                .neighbors
+                .iter()
+                .map(|n| self.valves[*n].name.as_str())
+                .collect::<Vec<&str>>();
+            writeln!(f, "{}", names.join(", "))?;
         }
         return Ok(());
     }
 }
 
-fn help_compute_permutations(arr: &mut Vec<usize>, k: usize, cb: &mut dyn FnMut(&Vec<usize>)) {
-    for i in k..arr.len() {
-        arr.swap(i, k);
-        help_compute_permutations(arr, k + 1, cb);
-        arr.swap(k, i);
+#[derive(Debug)]
+enum ParseError {
+    // line and column are 1-based
+    Malformed {
+        line: usize,
+        column: usize,
+        reason: String,
+    },
+    DuplicateValve {
+        line: usize,
+        column: usize,
+        name: String,
+    },
+    UnknownValve {
+        line: usize,
+        column: usize,
+        name: String,
+    },
+    NoStartValve(String),
+    TooManyValves(usize), // opened valves are bits in a u64
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseError::Malformed {
+                line,
+                column,
+                reason,
+            } => write!(f, "{}:{}: {}", line, column, reason),
+            ParseError::DuplicateValve { line, column, name } => {
+                write!(f, "{}:{}: valve {} is described twice", line, column, name)
+            }
+            ParseError::UnknownValve { line, column, name } => {
+                write!(
+                    f,
+                    "{}:{}: tunnel leads to unknown valve {}",
+                    line, column, name
+                )
+            }
+            ParseError::NoStartValve(name) => write!(f, "no start valve named {}", name),
+            ParseError::TooManyValves(count) => write!(
+                f,
+                "{} valves have a flow rate, at most {} are supported",
+                count, MAX_VALVES
+            ),
+        }
     }
+}
 
-    if k == arr.len() - 1 {
-        cb(arr);
+// walks a single line of the scan, keeping track of where we are for errors
+struct Cursor<'a> {
+    line: &'a str,
+    line_number: usize,
+    pos: usize, // byte offset into line
+}
+
+impl<'a> Cursor<'a> {
+    fn new(line: &'a str, line_number: usize) -> Cursor<'a> {
+        return Cursor {
+            line: line,
+            line_number: line_number,
+            pos: 0,
+        };
+    }
+
+    fn column(&self) -> usize {
+        return self.pos + 1;
+    }
+
+    fn rest(&self) -> &'a str {
+        return &self.line[self.pos..];
+    }
+
+    fn error(&self, reason: String) -> ParseError {
+        return ParseError::Malformed {
+            line: self.line_number,
+            column: self.column(),
+            reason: reason,
+        };
+    }
+
+    fn describe_next(&self) -> String {
+        return match self.rest().chars().next() {
+            Some(c) => format!("'{}'", c),
+            None => "end of line".to_string(),
+        };
+    }
+
+    fn skip_spaces(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    // accepts the first of words that matches, so list longer words first
+    fn expect_one_of(&mut self, words: &[&str]) -> Result<(), ParseError> {
+        self.skip_spaces();
+        for word in words {
+            if self.rest().starts_with(word) {
+                self.pos += word.len();
+                return Ok(());
+            }
+        }
+        return Err(self.error(format!(
+            "expected {} but found {}",
+            words.join(" or "),
+            self.describe_next()
+        )));
+    }
+
+    fn expect(&mut self, word: &str) -> Result<(), ParseError> {
+        return self.expect_one_of(&[word]);
+    }
+
+    // valve name and the column it started at
+    fn name(&mut self) -> Result<(String, usize), ParseError> {
+        self.skip_spaces();
+        let column = self.column();
+        let len = self
+            .rest()
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(self.rest().len());
+        if len == 0 {
+            return Err(self.error(format!(
+                "expected a valve name but found {}",
+                self.describe_next()
+            )));
+        }
+
+        let name = self.rest()[..len].to_string();
+        self.pos += len;
+        return Ok((name, column));
+    }
+
+    fn number(&mut self) -> Result<usize, ParseError> {
+        self.skip_spaces();
+        let len = self
+            .rest()
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.rest().len());
+        if len == 0 {
+            return Err(self.error(format!(
+                "expected a flow rate but found {}",
+                self.describe_next()
+            )));
+        }
+
+        let digits = &self.rest()[..len];
+        let number = digits
+            .parse()
+            .map_err(|_| self.error(format!("flow rate {} is too large", digits)))?;
+        self.pos += len;
+        return Ok(number);
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_spaces();
+        return self.rest().is_empty();
     }
 }
 
+// a valve as written in the scan, before neighbor names are resolved
+struct ValveLine {
+    line_number: usize,
+    name: String,
+    name_column: usize,
+    rate: usize,
+    neighbors: Vec<(String, usize)>, // name and column
+}
+
+// Valve X has flow rate=N; tunnel(s) lead(s) to valve(s) A, B
+fn parse_line(line: &str, line_number: usize) -> Result<ValveLine, ParseError> {
+    let mut cursor = Cursor::new(line, line_number);
+    cursor.expect("Valve")?;
+    let (name, name_column) = cursor.name()?;
+    cursor.expect("has")?;
+    cursor.expect("flow")?;
+    cursor.expect("rate")?;
+    cursor.expect("=")?;
+    let rate = cursor.number()?;
+    cursor.expect(";")?;
+    cursor.expect_one_of(&["tunnels", "tunnel"])?;
+    cursor.expect_one_of(&["leads", "lead"])?;
+    cursor.expect("to")?;
+    cursor.expect_one_of(&["valves", "valve"])?;
+
+    let mut neighbors = vec![cursor.name()?];
+    while !cursor.at_end() {
+        cursor.expect(",")?;
+        neighbors.push(cursor.name()?);
+    }
+
+    return Ok(ValveLine {
+        line_number: line_number,
+        name: name,
+        name_column: name_column,
+        rate: rate,
+        neighbors: neighbors,
+    });
+}
+
 impl World {
-    fn parse(contents: &str) -> World {
-        // read lines and compute bounds
-        let mut start_name: String = Default::default();
-        let mut valves: Vec<Valve> = vec![];
-        for line in contents.lines() {
-            let name = line[6..8].to_string();
-            if start_name == "" {
-                start_name = name.to_string();
+    fn parse(contents: &str, start_name: &str, timeout: usize) -> Result<World, ParseError> {
+        let mut valve_lines: Vec<ValveLine> = vec![];
+        for (i, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
             }
-            let (rate_str, tunnels_str) = line[23..].split_once(";").unwrap();
-            let rate: i32 = rate_str.parse().unwrap();
+            valve_lines.push(parse_line(line, i + 1)?);
+        }
 
-            let mut neighbors_str = Default::default();
-            match tunnels_str.split_once("valves ") {
-                Some(pair) => {
-                    neighbors_str = pair.1;
-                }
-                None => {}
+        // valves are stored in the order they are described
+        let mut indices: HashMap<&str, usize> = HashMap::new();
+        for (i, v) in valve_lines.iter().enumerate() {
+            if indices.insert(&v.name, i).is_some() {
+                return Err(ParseError::DuplicateValve {
+                    line: v.line_number,
+                    column: v.name_column,
+                    name: v.name.clone(),
+                });
             }
-            match tunnels_str.split_once("valve ") {
-                Some(pair) => {
-                    neighbors_str = pair.1;
-                }
-                None => {}
-            }
-
-            let nbrs = neighbors_str
-                .split(",")
-                .map(|n| n.trim().to_string())
-                .collect::<Vec<String>>();
+        }
 
+        let mut valves: Vec<Valve> = vec![];
+        for v in valve_lines.iter() {
             let mut nbr_indices: Vec<usize> = vec![];
-            for n in nbrs {
-                let mut has_match = false;
-                for (i, v) in valves.iter().enumerate() {
-                    if v.name == n {
-                        has_match = true;
-                        nbr_indices.push(i);
-                        break;
+            for (n, column) in v.neighbors.iter() {
+                match indices.get(n.as_str()) {
+                    Some(i) => nbr_indices.push(*i),
+                    None => {
+                        return Err(ParseError::UnknownValve {
+                            line: v.line_number,
+                            column: *column,
+                            name: n.clone(),
+                        });
                     }
                 }
-
-                if !has_match {
-                    // go ahead and create dummy neighbor
-                    nbr_indices.push(valves.len());
-                    valves.push(Valve {
-                        name: n,
-                        rate: -1,
-                        neighbors: Default::default(),
-                    })
-                }
-            }
-
-            // add to list of valves if it doesn't exist yet
-            let mut has_match = false;
-            for v in valves.iter_mut() {
-                if v.name == name {
-                    has_match = true;
-                    v.rate = rate;
-                    v.neighbors = nbr_indices.clone();
-                    break;
-                }
             }
 
-            if !has_match {
-                valves.push(Valve {
-                    name: name,
-                    rate: rate,
-                    neighbors: nbr_indices.clone(),
-                })
-            }
+            valves.push(Valve {
+                name: v.name.clone(),
+                rate: v.rate,
+                neighbors: nbr_indices,
+            });
         }
 
-        let start_index = valves.iter().position(|v| v.name == start_name).unwrap();
+        let start_index = match indices.get(start_name) {
+            Some(i) => *i,
+            None => return Err(ParseError::NoStartValve(start_name.to_string())),
+        };
 
         // permute order non-zero indices
         let mut to_visit: Vec<usize> = Default::default();
@@ -361,92 +552,527 @@ impl World {
                 to_visit.push(i);
             }
         }
+        if to_visit.len() > MAX_VALVES {
+            return Err(ParseError::TooManyValves(to_visit.len()));
+        }
 
         // compute next graph
-        let dists = compute_dists(&valves);
+        let (dists, next) = compute_dists(&valves);
 
-        println!("Built world");
-        return World {
+        return Ok(World {
             valves: valves,
             start_valve: start_index,
-            timeout: 30,
+            timeout: timeout,
             to_visit: to_visit,
             dists: dists,
-        };
+            next: next,
+        });
     }
 
-    fn next_route(&mut self, targets: &Vec<usize>) -> usize {
-        let mut t = 0;
+    // Valves not yet opened that can be reached and opened with time to spare,
+    // as (bit in the opened mask, valve, minutes left once it's open). Travel
+    // to unreachable valves saturates so they are always skipped
+    fn openings(
+        &self,
+        curr_index: usize,
+        time_left: usize,
+        opened: u64,
+    ) -> impl Iterator<Item = (u64, usize, usize)> + '_ {
+        return self
+            .to_visit
+            .iter()
+            .enumerate()
+            .filter_map(move |(bit, next_index)| {
+                if opened & (1 << bit) != 0 {
+                    return None;
+                }
+
+                // travel there and spend a minute opening it
+                let cost = self.dists[[curr_index, *next_index]].saturating_add(1);
+                if cost >= time_left {
+                    return None;
+                }
+                return Some((1 << bit, *next_index, time_left - cost));
+            });
+    }
+
+    // expand a route into what happens each minute until we run out of time
+    fn plan(&self, route: &[usize]) -> Vec<Minute> {
+        let mut actions: Vec<Action> = vec![];
         let mut curr_index = self.start_valve;
+        for next_index in route {
+            for hop in get_path(&self.next, curr_index, *next_index) {
+                actions.push(Action::Move(hop));
+            }
+            actions.push(Action::Open(*next_index));
+            curr_index = *next_index;
+        }
+
+        let mut minutes: Vec<Minute> = vec![];
+        let mut open: Vec<usize> = vec![];
         let mut released = 0;
+        for t in 0..self.timeout {
+            let releasing: usize = open.iter().map(|i| self.valves[*i].rate).sum();
+            released += releasing;
+            minutes.push(Minute {
+                open: open.clone(),
+                releasing: releasing,
+                released: released,
+                action: actions.get(t).copied(),
+            });
+
+            // opened valves only start releasing the next minute
+            if let Some(Action::Open(i)) = actions.get(t) {
+                open.push(*i);
+            }
+        }
+        return minutes;
+    }
+
+    // GraphViz rendering of the tunnels, valves worth opening are filled in and
+    // the route is drawn in red. When compressed only the start and the valves
+    // worth opening are drawn, with every pair joined by its travel distance
+    fn to_dot(&self, compressed: bool, route: &[usize]) -> String {
+        let mut nodes: Vec<usize> = vec![];
+        for i in 0..self.valves.len() {
+            if !compressed || i == self.start_valve || self.to_visit.contains(&i) {
+                nodes.push(i);
+            }
+        }
 
-        //println!("===");
-        //for t in targets {
-        //    print!("{:?},", self.valves[*t].name);
-        //}
-        //print!("\n");
-        for next_index in targets {
-            let next_t = t + self.dists[[curr_index, *next_index]] + 1;
-            if next_t >= 30 {
-                break;
+        // edges actually walked, in the order they are walked
+        let mut route_edges: Vec<(usize, usize)> = vec![];
+        let mut curr_index = self.start_valve;
+        for next_index in route {
+            if compressed {
+                route_edges.push((curr_index, *next_index));
+            } else {
+                for hop in get_path(&self.next, curr_index, *next_index) {
+                    route_edges.push((curr_index, hop));
+                    curr_index = hop;
+                }
             }
-            let will_release = ((30 - next_t) as i32) * (self.valves[*next_index].rate);
-            released += will_release;
-            //println!(
-            //    "{} to {}, travel from {}({}) to {}({}), will release {}*{}={}",
-            //    t,
-            //    next_t,
-            //    self.valves[curr_index].name,
-            //    curr_index,
-            //    self.valves[*next_index].name,
-            //    next_index,
-            //    (30 - t),
-            //    self.valves[*next_index].rate,
-            //    will_release
-            //);
-
-            t = next_t;
             curr_index = *next_index;
         }
 
-        // run simulation following current permutation rules
-        return released as usize;
+        let mut out = String::new();
+        writeln!(out, "graph valves {{").unwrap();
+        for i in nodes.iter() {
+            let v = &self.valves[*i];
+            write!(out, "    \"{}\" [label=\"{}\\n{}\"", v.name, v.name, v.rate).unwrap();
+            if self.to_visit.contains(i) {
+                write!(out, ", style=filled, fillcolor=gold").unwrap();
+            }
+            if *i == self.start_valve {
+                write!(out, ", shape=doublecircle").unwrap();
+            }
+            writeln!(out, "];").unwrap();
+        }
+
+        for (a, u) in nodes.iter().enumerate() {
+            for v in nodes[a + 1..].iter() {
+                if route_edges.contains(&(*u, *v)) || route_edges.contains(&(*v, *u)) {
+                    continue;
+                }
+
+                let (u_name, v_name) = (&self.valves[*u].name, &self.valves[*v].name);
+                if compressed && self.dists[[*u, *v]] != UNREACHABLE {
+                    writeln!(
+                        out,
+                        "    \"{}\" -- \"{}\" [label=\"{}\", color=gray];",
+                        u_name,
+                        v_name,
+                        self.dists[[*u, *v]]
+                    )
+                    .unwrap();
+                } else if self.valves[*u].neighbors.contains(v)
+                    || self.valves[*v].neighbors.contains(u)
+                {
+                    writeln!(out, "    \"{}\" -- \"{}\";", u_name, v_name).unwrap();
+                }
+            }
+        }
+
+        for (step, (u, v)) in route_edges.iter().enumerate() {
+            let mut label = format!("{}", step + 1);
+            if compressed {
+                label = format!("{} ({})", step + 1, self.dists[[*u, *v]]);
+            }
+            writeln!(
+                out,
+                "    \"{}\" -- \"{}\" [label=\"{}\", color=red, penwidth=2, dir=forward];",
+                self.valves[*u].name, self.valves[*v].name, label
+            )
+            .unwrap();
+        }
+
+        writeln!(out, "}}").unwrap();
+        return out;
+    }
+
+    fn route_names(&self, route: &[usize]) -> String {
+        return route
+            .iter()
+            .map(|i| self.valves[*i].name.as_str())
+            .collect::<Vec<&str>>()
+            .join(" -> ");
+    }
+}
+
+// one bit per valve worth opening, leaving room for the all valves mask
+const MAX_VALVES: usize = 63;
+
+// part 2 tries every way of splitting the valves in two, so this many valves
+// already means a million splits
+const MAX_SPLIT_VALVES: usize = 20;
+
+// memoized search over (current valve, minutes left, valves already opened),
+// bit i of the opened mask refers to world.to_visit[i]
+struct Solver<'a> {
+    world: &'a World,
+    memo: HashMap<(usize, usize, u64), usize>,
+}
+
+impl<'a> Solver<'a> {
+    fn new(world: &'a World) -> Solver<'a> {
+        return Solver {
+            world: world,
+            memo: HashMap::new(),
+        };
+    }
+
+    fn all_valves(&self) -> u64 {
+        return (1 << self.world.to_visit.len()) - 1;
+    }
+
+    // most pressure that can still be released from here, treating every valve
+    // in opened as unavailable
+    fn best_release(&mut self, curr_index: usize, time_left: usize, opened: u64) -> usize {
+        // valves too far away to open in time might as well already be open,
+        // which lets many more states share a memo entry
+        let world = self.world;
+        let in_reach = world
+            .openings(curr_index, time_left, 0)
+            .fold(0, |mask, (bit, _, _)| mask | bit);
+        let opened = opened | (self.all_valves() & !in_reach);
+        if let Some(released) = self.memo.get(&(curr_index, time_left, opened)) {
+            return *released;
+        }
+
+        let mut best = 0;
+        for (bit, next_index, remaining) in world.openings(curr_index, time_left, opened) {
+            let will_release = remaining * world.valves[next_index].rate;
+            let released = will_release + self.best_release(next_index, remaining, opened | bit);
+            best = cmp::max(best, released);
+        }
+
+        self.memo.insert((curr_index, time_left, opened), best);
+        return best;
+    }
+
+    // most pressure released from the start valve when only the valves in subset
+    // may be opened
+    fn best_for_subset(&mut self, time_left: usize, subset: u64) -> usize {
+        let opened = self.all_valves() & !subset;
+        return self.best_release(self.world.start_valve, time_left, opened);
+    }
+
+    // walk the memo table back down to recover the valve order that achieves
+    // best_for_subset
+    fn best_route(&mut self, time_left: usize, subset: u64) -> Vec<usize> {
+        let world = self.world;
+        let mut route = vec![];
+        let mut curr_index = world.start_valve;
+        let mut time_left = time_left;
+        let mut opened = self.all_valves() & !subset;
+        let mut best = self.best_release(curr_index, time_left, opened);
+        while best > 0 {
+            for (bit, next_index, remaining) in world.openings(curr_index, time_left, opened) {
+                let will_release = remaining * world.valves[next_index].rate;
+                let rest = self.best_release(next_index, remaining, opened | bit);
+                if will_release + rest == best {
+                    route.push(next_index);
+                    curr_index = next_index;
+                    time_left = remaining;
+                    opened |= bit;
+                    best = rest;
+                    break;
+                }
+            }
+        }
+        return route;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Action {
+    Move(usize),
+    Open(usize),
+}
+
+#[derive(Debug)]
+struct Minute {
+    open: Vec<usize>, // valves open during this minute
+    releasing: usize, // pressure released this minute
+    released: usize,  // pressure released so far, including this minute
+    action: Option<Action>,
+}
+
+// print a plan the same way the puzzle's walkthrough does, plus a running
+// total of pressure released at the end of each minute
+fn print_plan(world: &World, plan: &[Minute]) {
+    for (t, minute) in plan.iter().enumerate() {
+        if t > 0 {
+            println!();
+        }
+        println!("== Minute {} ==", t + 1);
+
+        let mut names = minute
+            .open
+            .iter()
+            .map(|i| world.valves[*i].name.as_str())
+            .collect::<Vec<&str>>();
+        names.sort();
+        match names.len() {
+            0 => println!("No valves are open."),
+            1 => println!(
+                "Valve {} is open, releasing {} pressure.",
+                names[0], minute.releasing
+            ),
+            2 => println!(
+                "Valves {} and {} are open, releasing {} pressure.",
+                names[0], names[1], minute.releasing
+            ),
+            n => println!(
+                "Valves {}, and {} are open, releasing {} pressure.",
+                names[..n - 1].join(", "),
+                names[n - 1],
+                minute.releasing
+            ),
+        }
+
+        match minute.action {
+            Some(Action::Move(i)) => println!("You move to valve {}.", world.valves[i].name),
+            Some(Action::Open(i)) => println!("You open valve {}.", world.valves[i].name),
+            None => {}
+        }
+
+        // not part of the puzzle's walkthrough, filter it out to diff
+        println!("Released so far: {}", minute.released);
+    }
+
+    if let Some(last) = plan.last() {
+        println!(
+            "This approach lets you release the most pressure possible in {} minutes with this valve layout, {}.",
+            plan.len(),
+            last.released
+        );
     }
 }
 
-fn part1(contents: &str) {
-    let mut world = World::parse(contents);
-    let mut targets = world.to_visit.clone();
+fn part1(world: &World, show_plan: bool) {
+    let mut solver = Solver::new(world);
 
+    let all_valves = solver.all_valves();
+    let max_released = solver.best_for_subset(world.timeout, all_valves);
+    let route = solver.best_route(world.timeout, all_valves);
+
+    println!("Max released: {}", max_released);
+    println!("Route: {}", world.route_names(&route));
+
+    if show_plan {
+        print_plan(world, &world.plan(&route));
+    }
+}
+
+fn part2(world: &World) -> Result<(), String> {
+    if world.to_visit.len() > MAX_SPLIT_VALVES {
+        return Err(format!(
+            "{} valves have a flow rate, at most {} can be split with the elephant",
+            world.to_visit.len(),
+            MAX_SPLIT_VALVES
+        ));
+    }
+
+    // 4 minutes go to teaching the elephant
+    let time_left = world.timeout.saturating_sub(4);
+    let mut solver = Solver::new(world);
+    let all_valves = solver.all_valves();
+
+    // we and the elephant open disjoint sets of valves, and there's never any
+    // harm in allowing one of us every valve the other can't open. So try each
+    // way of splitting the valves in two, each split once
     let mut max_released = 0;
-    let mut i: usize = 0;
-    let mut cb = |targets: &Vec<usize>| {
-        let released = world.next_route(&targets);
+    let mut best_split = 0;
+    for my_set in 0..=all_valves {
+        let elephant_set = all_valves & !my_set;
+        if my_set < elephant_set {
+            continue;
+        }
+
+        let released = solver.best_for_subset(time_left, my_set)
+            + solver.best_for_subset(time_left, elephant_set);
         if released > max_released {
             max_released = released;
+            best_split = my_set;
         }
+    }
+
+    let my_route = solver.best_route(time_left, best_split);
+    let elephant_route = solver.best_route(time_left, all_valves & !best_split);
+    println!("Max released with elephant: {}", max_released);
+    println!("You: {}", world.route_names(&my_route));
+    println!("Elephant: {}", world.route_names(&elephant_route));
+    return Ok(());
+}
 
-        if i % 1000000 == 0 {
-            println!(
-                "Route: {:+e}, Released: {}, Best: {}",
-                i, released, max_released
-            );
+fn main() {
+    let usage =
+        "Usage: <filename> [--time <minutes>] [--start <valve>] [--plan] [--dot [--compressed]]";
+    let mut fname = None;
+    let mut timeout: usize = 30;
+    let mut start_name = "AA".to_string();
+    let mut show_plan = false;
+    let mut show_dot = false;
+    let mut compressed = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--time" => {
+                timeout = args
+                    .next()
+                    .expect(usage)
+                    .parse()
+                    .expect("--time should be a number of minutes");
+            }
+            "--start" => {
+                start_name = args.next().expect(usage);
+            }
+            "--plan" => {
+                show_plan = true;
+            }
+            "--dot" => {
+                show_dot = true;
+            }
+            "--compressed" => {
+                compressed = true;
+            }
+            _ => {
+                fname = Some(arg);
+            }
         }
-        i += 1;
-    };
+    }
 
-    help_compute_permutations(&mut targets, 0, &mut cb);
+    let contents =
+        fs::read_to_string(fname.expect(usage)).expect("Should have been able to read the file");
+    let world = World::parse(&contents, &start_name, timeout).unwrap_or_else(|e| panic!("{}", e));
 
-    println!("Max released: {}", max_released);
-    // find fastest path
+    if show_dot {
+        let mut solver = Solver::new(&world);
+        let all_valves = solver.all_valves();
+        let route = solver.best_route(world.timeout, all_valves);
+        print!("{}", world.to_dot(compressed, &route));
+        return;
+    }
+
+    part1(&world, show_plan);
+    part2(&world).unwrap_or_else(|e| panic!("{}", e));
 }
 
-fn part2(contents: &str) {}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-fn main() {
-    let fname = env::args().nth(1).expect("Should pass 1 filename arg");
-    let contents = fs::read_to_string(fname).expect("Should have been able to read the file");
+    fn world(contents: &str) -> World {
+        return World::parse(contents, "AA", 30).unwrap();
+    }
+
+    fn index(world: &World, name: &str) -> usize {
+        return world.valves.iter().position(|v| v.name == name).unwrap();
+    }
+
+    // a line of valves each joined to the one before and after
+    fn chain(n: usize) -> Vec<Valve> {
+        return (0..n)
+            .map(|i| Valve {
+                name: format!("V{}", i),
+                rate: 1,
+                neighbors: [i.checked_sub(1), Some(i + 1).filter(|j| *j < n)]
+                    .into_iter()
+                    .flatten()
+                    .collect(),
+            })
+            .collect();
+    }
+
+    #[test]
+    fn chain_distances_are_exact() {
+        let n = 12;
+        let (dist, next) = compute_dists(&chain(n));
+        for u in 0..n {
+            for v in 0..n {
+                assert_eq!(dist[[u, v]], u.abs_diff(v));
+            }
+        }
+
+        let path: Vec<usize> = get_path(&next, 0, n - 1).into_iter().collect();
+        assert_eq!(path, (1..n).collect::<Vec<usize>>());
+        let path: Vec<usize> = get_path(&next, n - 1, 0).into_iter().collect();
+        assert_eq!(path, (0..n - 1).rev().collect::<Vec<usize>>());
+    }
 
-    part1(&contents);
-    //part2(&contents);
+    #[test]
+    fn unreachable_pairs_are_marked() {
+        let world = world(include_str!("../disconnected.txt"));
+        for from in ["AA", "BB", "CC", "DD"] {
+            for to in ["EE", "FF", "GG", "HH"] {
+                let (u, v) = (index(&world, from), index(&world, to));
+                for (a, b) in [(u, v), (v, u)] {
+                    assert_eq!(world.dists[[a, b]], UNREACHABLE);
+                    assert_eq!(world.next[[a, b]], UNREACHABLE);
+                    assert!(get_path(&world.next, a, b).is_empty());
+                }
+            }
+        }
+
+        let (ee, gg) = (index(&world, "EE"), index(&world, "GG"));
+        assert_eq!(world.dists[[ee, gg]], 2);
+        assert_eq!(get_path(&world.next, ee, gg), [index(&world, "FF"), gg]);
+    }
+
+    #[test]
+    fn self_loop_valve() {
+        let world = world(include_str!("../disconnected.txt"));
+        let hh = index(&world, "HH");
+        assert_eq!(world.dists[[hh, hh]], 0);
+        assert_eq!(world.next[[hh, hh]], hh);
+        assert!(get_path(&world.next, hh, hh).is_empty());
+    }
+
+    #[test]
+    fn never_routes_to_other_components() {
+        let world = world(include_str!("../disconnected.txt"));
+        let mut solver = Solver::new(&world);
+        let all_valves = solver.all_valves();
+
+        // only the valves joined to AA are worth anything
+        let mut reachable = 0;
+        for (bit, i) in world.to_visit.iter().enumerate() {
+            if world.dists[[world.start_valve, *i]] != UNREACHABLE {
+                reachable |= 1 << bit;
+            }
+        }
+        assert_eq!(
+            solver.best_for_subset(30, all_valves),
+            solver.best_for_subset(30, reachable)
+        );
+
+        let route = solver.best_route(30, all_valves);
+        assert!(!route.is_empty());
+        for i in route {
+            let name = world.valves[i].name.as_str();
+            assert!(["BB", "CC", "DD"].contains(&name), "routed to {}", name);
+        }
+    }
 }